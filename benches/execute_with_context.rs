@@ -0,0 +1,100 @@
+//! Counts the allocations of one background run through
+//! `AsyncCommandHandler::execute_with_context`.
+//!
+//! - `new app` is the `TerminalApp::new` the default shim used to build per run.
+//! - `shim` is a handler that only implements `execute_async`, so every run goes
+//!   through the default shim and its `TerminalApp::detached`.
+//! - `override` is a handler that overrides `execute_with_context` and never
+//!   builds an app.
+//!
+//! Run with `cargo bench --bench execute_with_context`.
+
+use async_trait::async_trait;
+use daemon_console::command::CommandContext;
+use daemon_console::{AsyncCommandHandler, TerminalApp};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio_util::sync::CancellationToken;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const RUNS: usize = 10_000;
+
+/// A handler written against `execute_async` only.
+struct Legacy;
+
+#[async_trait]
+impl AsyncCommandHandler for Legacy {
+    async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+        String::new()
+    }
+
+    fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+        Box::new(Legacy)
+    }
+}
+
+/// A handler that works from the context alone.
+struct Lightweight;
+
+#[async_trait]
+impl AsyncCommandHandler for Lightweight {
+    async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+        String::new()
+    }
+
+    async fn execute_with_context(&mut self, _ctx: &CommandContext, _args: &[&str]) -> String {
+        String::new()
+    }
+
+    fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+        Box::new(Lightweight)
+    }
+}
+
+fn measure(name: &str, mut run: impl FnMut()) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = BYTES.load(Ordering::Relaxed);
+    for _ in 0..RUNS {
+        run();
+    }
+    println!(
+        "{:<10} {:>6.1} allocations {:>8.0} bytes per run",
+        name,
+        (ALLOCATIONS.load(Ordering::Relaxed) - allocations) as f64 / RUNS as f64,
+        (BYTES.load(Ordering::Relaxed) - bytes) as f64 / RUNS as f64,
+    );
+}
+
+fn main() {
+    let console = TerminalApp::new();
+    let ctx = CommandContext::new(Some(console.get_action_sender()), CancellationToken::new());
+
+    measure("new app", || drop(TerminalApp::new()));
+    let mut legacy = Legacy;
+    measure("shim", || {
+        futures::executor::block_on(legacy.execute_with_context(&ctx, &[]));
+    });
+    let mut lightweight = Lightweight;
+    measure("override", || {
+        futures::executor::block_on(lightweight.execute_with_context(&ctx, &[]));
+    });
+}
@@ -0,0 +1,208 @@
+//! Typed access to command arguments.
+//!
+//! Handlers still receive `&[&str]`; wrap it in [`Args`] to parse positional
+//! arguments and `--flags` with uniform error messages.
+
+use crate::TerminalApp;
+use std::fmt;
+use std::str::FromStr;
+
+/// Error produced when a command argument is missing or cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgError {
+    /// No positional argument at this (zero-based) index.
+    Missing { index: usize },
+    /// The argument could not be parsed as the requested type.
+    Invalid {
+        index: usize,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgError::Missing { index } => write!(f, "Missing argument #{}", index + 1),
+            ArgError::Invalid {
+                index,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid argument #{} '{}': expected {}",
+                index + 1,
+                value,
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+/// Read-only view over a command's arguments.
+///
+/// Arguments starting with `--` are flags; everything else is positional, and
+/// [`get`](Self::get) and [`rest`](Self::rest) index positional arguments only.
+///
+/// # Examples
+///
+/// The `wait` command from the bundled binary, written with `Args`:
+///
+/// ```
+/// use async_trait::async_trait;
+/// use daemon_console::{AsyncCommandHandler, TerminalApp, args::Args, get_error, get_info};
+/// use std::time::Duration;
+///
+/// #[derive(Clone)]
+/// struct SleepCommand;
+///
+/// #[async_trait]
+/// impl AsyncCommandHandler for SleepCommand {
+///     async fn execute_async(&mut self, app: &mut TerminalApp, args: &[&str]) -> String {
+///         let seconds: u64 = match Args::new(args).get(0) {
+///             Ok(seconds) => seconds,
+///             Err(e) => return get_error!(&format!("{e}. Usage: wait <seconds>"), "CommandResp"),
+///         };
+///         tokio::time::sleep(Duration::from_secs(seconds)).await;
+///         app.info("Wake up!");
+///         get_info!(&format!("Finished sleeping for {} seconds!", seconds), "CommandResp")
+///     }
+///
+///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+///         Box::new(self.clone())
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Args<'a> {
+    raw: &'a [&'a str],
+}
+
+impl<'a> Args<'a> {
+    /// Wraps a handler's raw argument slice.
+    pub fn new(raw: &'a [&'a str]) -> Self {
+        Self { raw }
+    }
+
+    /// Returns the raw arguments, flags included.
+    pub fn raw(&self) -> &'a [&'a str] {
+        self.raw
+    }
+
+    /// Returns the positional arguments in order.
+    pub fn positional(&self) -> Vec<&'a str> {
+        self.raw
+            .iter()
+            .copied()
+            .filter(|arg| !arg.starts_with("--"))
+            .collect()
+    }
+
+    /// Returns the number of positional arguments.
+    pub fn len(&self) -> usize {
+        self.positional().len()
+    }
+
+    /// Returns whether there are no positional arguments.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Parses the positional argument at `index`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ArgError::Missing`] when there is no such argument and
+    /// [`ArgError::Invalid`] when it does not parse as `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::args::{ArgError, Args};
+    ///
+    /// let args = Args::new(&["--force", "3", "abc"]);
+    /// assert_eq!(args.get::<u64>(0), Ok(3));
+    /// assert_eq!(args.get::<String>(1).as_deref(), Ok("abc"));
+    /// assert_eq!(args.get::<u64>(2), Err(ArgError::Missing { index: 2 }));
+    /// assert_eq!(
+    ///     args.get::<u64>(1).unwrap_err().to_string(),
+    ///     "Invalid argument #2 'abc': expected u64"
+    /// );
+    /// assert!(args.flag("--force"));
+    /// ```
+    pub fn get<T: FromStr>(&self, index: usize) -> Result<T, ArgError> {
+        let value = self
+            .positional()
+            .get(index)
+            .copied()
+            .ok_or(ArgError::Missing { index })?;
+        value.parse().map_err(|_| ArgError::Invalid {
+            index,
+            value: value.to_string(),
+            expected: short_type_name::<T>(),
+        })
+    }
+
+    /// Parses the positional argument at `index`, or returns `default` when absent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ArgError::Invalid`] when the argument is present but does not parse.
+    pub fn get_or<T: FromStr>(&self, index: usize, default: T) -> Result<T, ArgError> {
+        match self.get(index) {
+            Err(ArgError::Missing { .. }) => Ok(default),
+            other => other,
+        }
+    }
+
+    /// Returns whether the flag (e.g. `"--confirm"`) was given.
+    pub fn flag(&self, name: &str) -> bool {
+        self.raw.contains(&name)
+    }
+
+    /// Returns the positional arguments from `index` on, e.g. a free-text message.
+    pub fn rest(&self, index: usize) -> Vec<&'a str> {
+        self.positional().into_iter().skip(index).collect()
+    }
+}
+
+impl<'a> From<&'a [&'a str]> for Args<'a> {
+    fn from(raw: &'a [&'a str]) -> Self {
+        Self::new(raw)
+    }
+}
+
+/// Adapts a handler taking [`Args`] to the raw-slice handler signature.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::{TerminalApp, args::{Args, with_args}};
+///
+/// let mut app = TerminalApp::new();
+/// app.register_handler(
+///     "greet",
+///     with_args(|_app: &mut TerminalApp, args: Args| {
+///         let name: String = args.get_or(0, "world".to_string()).unwrap();
+///         if args.flag("--shout") {
+///             format!("HELLO, {}!", name.to_uppercase())
+///         } else {
+///             format!("Hello, {}!", name)
+///         }
+///     }),
+/// );
+/// ```
+pub fn with_args<F>(mut handler: F) -> impl FnMut(&mut TerminalApp, &[&str]) -> String
+where
+    F: FnMut(&mut TerminalApp, Args) -> String + Send + Sync + 'static,
+{
+    move |app: &mut TerminalApp, raw: &[&str]| handler(app, Args::new(raw))
+}
+
+/// Returns the last path segment of a type name, e.g. `u64` or `String`.
+fn short_type_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
@@ -0,0 +1,291 @@
+//! Optional built-in commands.
+//!
+//! Enabled with [`ConsoleCore::set_builtin_commands`](crate::ConsoleCore::set_builtin_commands).
+
+use crate::logger::{self, LogLevel, Theme};
+use crate::{ConsoleCore, TerminalApp, get_error, get_info, utils};
+
+/// Names of all built-in commands.
+pub const BUILTIN_COMMANDS: &[&str] = &[
+    "clearcache",
+    "config",
+    "keys",
+    "loglevel",
+    "quiet",
+    "stats",
+    "tail",
+    "theme",
+    "verbose",
+];
+
+/// Registers every built-in command with the console.
+pub(crate) fn register_builtins(core: &mut ConsoleCore) {
+    core.register_builtin("clearcache", Box::new(clearcache));
+    core.register_builtin("config", Box::new(config));
+    core.register_builtin("keys", Box::new(keys));
+    core.register_builtin("loglevel", Box::new(loglevel));
+    core.register_builtin("quiet", Box::new(quiet));
+    core.register_builtin("stats", Box::new(stats));
+    core.register_builtin("tail", Box::new(tail));
+    core.register_builtin("theme", Box::new(theme));
+    core.register_builtin("verbose", Box::new(verbose));
+}
+
+/// `clearcache`: drops all cached command output.
+fn clearcache(app: &mut TerminalApp, _: &[&str]) -> String {
+    let count = app.clear_command_cache();
+    get_info!(
+        &format!("Cleared {} cached command output(s)", count),
+        "CommandResp"
+    )
+}
+
+/// `config`: prints the effective console configuration.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use daemon_console::command::execute_command;
+/// use daemon_console::{ConsoleOutput, TerminalApp, utils};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut app = TerminalApp::new();
+///     app.set_output(ConsoleOutput::Sink);
+///     app.set_builtin_commands(true);
+///
+///     execute_command(&mut app, "loglevel warn").await;
+///     let output = utils::strip_ansi(&execute_command(&mut app, "config").await);
+///     assert!(output.contains("min log level: WARN"));
+/// }
+/// ```
+fn config(app: &mut TerminalApp, _: &[&str]) -> String {
+    app.config_lines()
+        .iter()
+        .map(|line| get_info!(line, "CommandResp"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `keys`: lists the editor keybindings.
+///
+/// # Examples
+///
+/// ```
+/// use crossterm::event::KeyCode;
+/// use daemon_console::command::execute_command;
+/// use daemon_console::keys::{KeyAction, KeyBinding};
+/// use daemon_console::{ConsoleOutput, TerminalApp, utils};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut app = TerminalApp::new();
+///     app.set_output(ConsoleOutput::Sink);
+///     app.set_builtin_commands(true);
+///     app.set_keybinding(KeyBinding::ctrl(KeyCode::Char('q')), KeyAction::Exit);
+///
+///     let output = utils::strip_ansi(&execute_command(&mut app, "keys").await);
+///     assert!(output.contains("Ctrl+D      exit the console"));
+///     assert!(output.contains("Ctrl+Q      exit the console"));
+///     assert!(output.contains("Up          previous history entry"));
+/// }
+/// ```
+fn keys(app: &mut TerminalApp, _: &[&str]) -> String {
+    let bindings: Vec<_> = app
+        .keybindings()
+        .iter()
+        .map(|(key, action)| (key.to_string(), action.description()))
+        .collect();
+    if bindings.is_empty() {
+        return get_info!("No keys are bound", "CommandResp");
+    }
+    let width = bindings.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    bindings
+        .into_iter()
+        .map(|(key, description)| {
+            get_info!(&format!("{:<width$}  {}", key, description), "CommandResp")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `loglevel [level]`: shows or sets the global minimum log level.
+fn loglevel(app: &mut TerminalApp, args: &[&str]) -> String {
+    let Some(name) = args.first() else {
+        return get_info!(
+            &format!("Current log level: {}", logger::min_level()),
+            "CommandResp"
+        );
+    };
+    match name.parse::<LogLevel>() {
+        Ok(level) => {
+            logger::set_min_level(level);
+            get_info!(&format!("Log level set to {}", level), "CommandResp")
+        }
+        Err(e) => {
+            app.mark_command_failed();
+            get_error!(&e.to_string(), "CommandResp")
+        }
+    }
+}
+
+/// `quiet`: hides low-severity log entries from the terminal.
+fn quiet(app: &mut TerminalApp, _: &[&str]) -> String {
+    app.set_quiet(true);
+    get_info!(
+        "Quiet mode enabled, use 'verbose' to show all logs again",
+        "CommandResp"
+    )
+}
+
+/// Number of lines `tail` prints without an argument.
+const DEFAULT_TAIL_LINES: usize = 20;
+
+/// `tail [n]`: prints the last `n` lines of [`recent_logs`](TerminalApp::recent_logs).
+///
+/// Prints every buffered line when fewer than `n` are kept. Styling is removed
+/// while colors are disabled.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::command::execute_command;
+/// use daemon_console::{ConsoleOutput, TerminalApp, utils};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut app = TerminalApp::new();
+///     app.set_output(ConsoleOutput::Sink);
+///     app.set_builtin_commands(true);
+///     for step in ["fetch", "build", "deploy"] {
+///         app.info(step);
+///     }
+///
+///     let output = utils::strip_ansi(&execute_command(&mut app, "tail 2").await);
+///     assert!(!output.contains("fetch"));
+///     assert!(output.contains("build") && output.contains("deploy"));
+///
+///     let output = execute_command(&mut app, "tail 100").await;
+///     assert_eq!(output.lines().count(), 3);
+/// }
+/// ```
+fn tail(app: &mut TerminalApp, args: &[&str]) -> String {
+    let count = match args.first().map(|n| n.parse::<usize>()) {
+        None => DEFAULT_TAIL_LINES,
+        Some(Ok(count)) => count,
+        Some(Err(_)) => {
+            app.mark_command_failed();
+            return get_error!(&format!("Invalid line count '{}'", args[0]), "CommandResp");
+        }
+    };
+    let lines: Vec<_> = app.recent_logs().collect();
+    let shown = &lines[lines.len().saturating_sub(count)..];
+    if shown.is_empty() {
+        return get_info!("No log lines buffered", "CommandResp");
+    }
+    shown
+        .iter()
+        .map(|line| {
+            if logger::color_enabled() {
+                line.to_string()
+            } else {
+                utils::strip_ansi(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `theme [name]`: lists the built-in color themes or switches to one.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use daemon_console::command::execute_command;
+/// use daemon_console::logger::{self, Theme};
+/// use daemon_console::{ConsoleOutput, TerminalApp, utils};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut app = TerminalApp::new();
+///     app.set_output(ConsoleOutput::Sink);
+///     app.set_builtin_commands(true);
+///
+///     let listing = utils::strip_ansi(&execute_command(&mut app, "theme").await);
+///     assert!(listing.contains("* default"));
+///     assert!(listing.contains("  monochrome"));
+///
+///     execute_command(&mut app, "theme high-contrast").await;
+///     assert_eq!(logger::theme(), Theme::HIGH_CONTRAST);
+///     execute_command(&mut app, "theme neon").await;
+///     assert!(!app.last_command_succeeded());
+/// }
+/// ```
+fn theme(app: &mut TerminalApp, args: &[&str]) -> String {
+    let Some(name) = args.first() else {
+        let active = logger::theme();
+        let mut lines = vec![get_info!(
+            &format!("Active theme: {}", active.name),
+            "CommandResp"
+        )];
+        lines.extend(Theme::BUILTIN.iter().map(|theme| {
+            let marker = if *theme == active { '*' } else { ' ' };
+            get_info!(&format!("{} {}", marker, theme.name), "CommandResp")
+        }));
+        return lines.join("\n");
+    };
+    match Theme::by_name(name) {
+        Some(theme) => {
+            logger::set_theme(theme);
+            get_info!(&format!("Theme set to {}", theme.name), "CommandResp")
+        }
+        None => {
+            app.mark_command_failed();
+            let names: Vec<_> = Theme::BUILTIN.iter().map(|theme| theme.name).collect();
+            get_error!(
+                &format!(
+                    "Unknown theme '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                ),
+                "CommandResp"
+            )
+        }
+    }
+}
+
+/// `verbose`: leaves quiet mode.
+fn verbose(app: &mut TerminalApp, _: &[&str]) -> String {
+    app.set_quiet(false);
+    get_info!("Quiet mode disabled", "CommandResp")
+}
+
+/// `stats`: lists how often and how long each command has run.
+fn stats(app: &mut TerminalApp, _: &[&str]) -> String {
+    let mut entries: Vec<_> = app.command_stats().iter().collect();
+    if entries.is_empty() {
+        return get_info!("No commands recorded yet", "CommandResp");
+    }
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    let width = entries
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    entries
+        .into_iter()
+        .map(|(name, stats)| {
+            get_info!(
+                &format!(
+                    "{:<width$}  runs: {:>5}  avg: {:>10.3?}  total: {:.3?}",
+                    name,
+                    stats.count,
+                    stats.average_duration(),
+                    stats.total_duration,
+                ),
+                "CommandResp"
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -1,13 +1,102 @@
-use crate::{TerminalApp, get_error, get_info, get_warn};
+use crate::logger::color_enabled;
+use crate::utils::strip_ansi;
+use crate::{AppAction, ConsoleHandle, TerminalApp, get_error, get_info, get_warn};
 use async_trait::async_trait;
+use crossterm::style::{Attribute, Color, ResetColor, SetForegroundColor};
 use futures::future::BoxFuture;
+use std::fmt;
+use std::time::{Duration, Instant};
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 /// Result from command execution
-#[derive(Debug)]
+///
+/// Build one with [`new`](Self::new) or with `..Default::default()`, so later
+/// fields do not break the literal.
+#[derive(Debug, Clone, Default)]
 pub struct CommandResult {
     pub command: String,
     pub output: String,
+    /// Correlation id of the `CommandStarted` event, for commands submitted at
+    /// the prompt.
+    pub correlation_id: Option<String>,
+    /// How long the command ran, when known.
+    pub duration: Option<Duration>,
+}
+
+impl CommandResult {
+    /// Creates a result without correlation id or duration.
+    pub fn new(command: impl Into<String>, output: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            output: output.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the correlation id of the `CommandStarted` event.
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    /// Sets how long the command ran.
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Output lines with ANSI styling and surrounding whitespace removed.
+    fn plain_lines(&self) -> Vec<String> {
+        strip_ansi(self.output.trim())
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+
+    /// Returns a one-line summary: the command and the first output line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::CommandResult;
+    ///
+    /// let result = CommandResult::new("status", "  all good\n  3 workers\n")
+    ///     .with_correlation_id("1a2b3c4d");
+    /// assert_eq!(result.summary(), "status: all good (+1 more line)");
+    /// assert_eq!(result.to_string(), "status:\n  all good\n  3 workers");
+    /// ```
+    pub fn summary(&self) -> String {
+        let lines = self.plain_lines();
+        match lines.len() {
+            0 => format!("{} (no output)", self.command),
+            1 => format!("{}: {}", self.command, lines[0]),
+            2 => format!("{}: {} (+1 more line)", self.command, lines[0]),
+            n => format!("{}: {} (+{} more lines)", self.command, lines[0], n - 1),
+        }
+    }
+}
+
+impl fmt::Display for CommandResult {
+    /// Renders the command followed by its output without ANSI styling.
+    ///
+    /// Single-line output stays on the command's line; longer output is indented
+    /// below it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self.plain_lines();
+        match lines.as_slice() {
+            [] => write!(f, "{} (no output)", self.command),
+            [line] => write!(f, "{}: {}", self.command, line),
+            lines => {
+                write!(f, "{}:", self.command)?;
+                for line in lines {
+                    write!(f, "\n  {}", line)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 /// Trait for synchronous command handlers that can be registered with the terminal application.
@@ -26,6 +115,99 @@ pub trait CommandHandler: Send + Sync + 'static {
     ///
     /// String output to be displayed to the user
     fn execute(&mut self, app: &mut TerminalApp, args: &[&str]) -> String;
+
+    /// Executes the command, returning structured output for other tooling.
+    ///
+    /// Called before [`execute`](Self::execute), which only runs when this returns
+    /// `None` (the default). A returned value is rendered for humans (a JSON string
+    /// as its plain text, anything else as pretty-printed JSON) and is also
+    /// emitted unchanged in a
+    /// [`CommandOutput`](crate::events::DaemonConsoleEvent::CommandOutput) event for
+    /// machines. Only available with the `serde` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::command::{CommandHandler, execute_command};
+    /// use daemon_console::events::DaemonConsoleEvent;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    /// use serde_json::{Value, json};
+    ///
+    /// struct Workers;
+    ///
+    /// impl CommandHandler for Workers {
+    ///     fn execute(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+    ///         unreachable!("structured output is always available")
+    ///     }
+    ///
+    ///     fn execute_value(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> Option<Value> {
+    ///         Some(json!({ "busy": 2, "idle": 1 }))
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     let mut events = app.subscribe_events().unwrap();
+    ///     app.register_command("workers", Box::new(Workers));
+    ///
+    ///     let text = execute_command(&mut app, "workers").await;
+    ///     assert_eq!(text, "{\n  \"busy\": 2,\n  \"idle\": 1\n}");
+    ///     assert!(matches!(
+    ///         events.try_recv(),
+    ///         Ok(DaemonConsoleEvent::CommandOutput { value, .. }) if value["busy"] == 2
+    ///     ));
+    /// }
+    /// ```
+    #[cfg(feature = "serde")]
+    fn execute_value(&mut self, app: &mut TerminalApp, args: &[&str]) -> Option<serde_json::Value> {
+        let _ = (app, args);
+        None
+    }
+}
+
+/// Runs a sync handler for `command`, preferring its structured output when it has any.
+///
+/// Log groups the handler leaves open are closed once it returns.
+fn execute_sync(
+    handler: &mut Box<dyn CommandHandler>,
+    app: &mut TerminalApp,
+    command: &str,
+    args: &[&str],
+) -> String {
+    let depth = app.log_group_depth();
+    let output = execute_sync_handler(handler, app, command, args);
+    app.close_log_groups(depth);
+    output
+}
+
+fn execute_sync_handler(
+    handler: &mut Box<dyn CommandHandler>,
+    app: &mut TerminalApp,
+    command: &str,
+    args: &[&str],
+) -> String {
+    #[cfg(feature = "serde")]
+    if let Some(value) = handler.execute_value(app, args) {
+        let text = match &value {
+            serde_json::Value::String(text) => text.clone(),
+            value => serde_json::to_string_pretty(value).unwrap_or_default(),
+        };
+        if app.events_wanted() {
+            let correlation_id = app.current_correlation_id.clone();
+            app.emit_events(crate::events::DaemonConsoleEvent::CommandOutput {
+                command: command.to_string(),
+                value,
+                correlation_id,
+                timestamp: crate::events::DaemonConsoleEvent::now_ts(),
+            });
+        }
+        return text;
+    }
+    #[cfg(not(feature = "serde"))]
+    let _ = command;
+    handler.execute(app, args)
 }
 
 /// Trait for asynchronous command handlers that can be registered with the terminal application.
@@ -46,10 +228,313 @@ pub trait AsyncCommandHandler: Send + Sync + 'static {
     /// String output to be displayed to the user
     async fn execute_async(&mut self, app: &mut TerminalApp, args: &[&str]) -> String;
 
+    /// Executes the command with a token that trips when the command is cancelled.
+    ///
+    /// Background runs call this method. Cancellation is cooperative: when the
+    /// command is cancelled (see `ConsoleCore::cancel_command`), the token is
+    /// cancelled first and the task is only aborted if it is still running after
+    /// [`CANCEL_GRACE_PERIOD`](crate::console_core::CANCEL_GRACE_PERIOD). Override
+    /// this to watch the token and release files or locks before returning; the
+    /// default ignores the token and calls [`execute_async`](Self::execute_async).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use daemon_console::{AsyncCommandHandler, ConsoleOutput, TerminalApp, command::execute_command};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::time::Duration;
+    /// use tokio_util::sync::CancellationToken;
+    ///
+    /// #[derive(Clone)]
+    /// struct Watch {
+    ///     cleaned_up: Arc<AtomicBool>,
+    /// }
+    ///
+    /// #[async_trait]
+    /// impl AsyncCommandHandler for Watch {
+    ///     async fn execute_async(&mut self, app: &mut TerminalApp, args: &[&str]) -> String {
+    ///         self.execute_cancellable(app, args, CancellationToken::new()).await
+    ///     }
+    ///
+    ///     async fn execute_cancellable(
+    ///         &mut self,
+    ///         _app: &mut TerminalApp,
+    ///         _args: &[&str],
+    ///         cancel: CancellationToken,
+    ///     ) -> String {
+    ///         cancel.cancelled().await;
+    ///         self.cleaned_up.store(true, Ordering::SeqCst);
+    ///         "watch stopped".to_string()
+    ///     }
+    ///
+    ///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+    ///         Box::new(self.clone())
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let cleaned_up = Arc::new(AtomicBool::new(false));
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_async_command("watch", Box::new(Watch { cleaned_up: cleaned_up.clone() }));
+    ///
+    ///     execute_command(&mut app, "watch").await;
+    ///     assert_eq!(app.cancel_command("watch"), 1);
+    ///     while app.is_running("watch") {
+    ///         tokio::time::sleep(Duration::from_millis(10)).await;
+    ///     }
+    ///     assert!(cleaned_up.load(Ordering::SeqCst));
+    /// }
+    /// ```
+    async fn execute_cancellable(
+        &mut self,
+        app: &mut TerminalApp,
+        args: &[&str],
+        _cancel: CancellationToken,
+    ) -> String {
+        self.execute_async(app, args).await
+    }
+
+    /// Executes a background run with a lightweight [`CommandContext`].
+    ///
+    /// This is what background runs actually call. The default is a compatibility
+    /// shim: it builds a temporary [`TerminalApp::detached`] connected to the
+    /// console's action channel and calls
+    /// [`execute_cancellable`](Self::execute_cancellable), so handlers written
+    /// against `execute_async` keep working. The detached app is small (no
+    /// channels, output buffer or terminal query), but it is still built on every
+    /// run; handlers that only log and watch for cancellation can override this
+    /// to skip it, see `benches/execute_with_context.rs`. Foreground runs
+    /// (e.g. `execute_command_to_completion`) still call `execute_async` with the
+    /// real app.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use daemon_console::command::{CommandContext, execute_command};
+    /// use daemon_console::{AsyncCommandHandler, ConsoleOutput, TerminalApp};
+    ///
+    /// #[derive(Clone)]
+    /// struct Ping;
+    ///
+    /// #[async_trait]
+    /// impl AsyncCommandHandler for Ping {
+    ///     async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+    ///         "pong".to_string()
+    ///     }
+    ///
+    ///     async fn execute_with_context(&mut self, ctx: &CommandContext, _args: &[&str]) -> String {
+    ///         ctx.info("Pinging without a temporary app");
+    ///         "pong".to_string()
+    ///     }
+    ///
+    ///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+    ///         Box::new(self.clone())
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_async_command("ping", Box::new(Ping));
+    ///     execute_command(&mut app, "ping").await;
+    ///
+    ///     while app.process_command_results().await.unwrap() == 0 {
+    ///         tokio::task::yield_now().await;
+    ///     }
+    ///     app.process_pending_actions();
+    ///     assert_eq!(app.last_output(), Some("pong"));
+    ///     assert_eq!(app.metrics().info_logs, 1);
+    /// }
+    /// ```
+    async fn execute_with_context(&mut self, ctx: &CommandContext, args: &[&str]) -> String {
+        let mut app = TerminalApp::detached(ctx.handle().cloned());
+        self.execute_cancellable(&mut app, args, ctx.cancel_token().clone())
+            .await
+    }
+
     /// Creates a boxed clone of this handler for reuse
     fn box_clone(&self) -> Box<dyn AsyncCommandHandler>;
 }
 
+/// What a background async command gets instead of a `TerminalApp`.
+///
+/// Passed to [`AsyncCommandHandler::execute_with_context`]. Log calls are sent
+/// to the console as [`AppAction`]s and rendered by its loop; in bounded mode
+/// they are dropped while the action queue is full.
+#[derive(Clone, Debug)]
+pub struct CommandContext {
+    handle: Option<ConsoleHandle>,
+    cancel: CancellationToken,
+}
+
+impl CommandContext {
+    /// Creates a context sending to `handle`, cancelled through `cancel`.
+    pub fn new(handle: Option<ConsoleHandle>, cancel: CancellationToken) -> Self {
+        Self { handle, cancel }
+    }
+
+    /// Returns the handle for sending actions to the console, if it has one.
+    pub fn handle(&self) -> Option<&ConsoleHandle> {
+        self.handle.as_ref()
+    }
+
+    /// Returns the token that trips when the command is cancelled.
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Returns whether the command has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    fn send(&self, action: AppAction) {
+        if let Some(handle) = &self.handle {
+            let _ = handle.try_send(action);
+        }
+    }
+
+    /// Logs an info-level message on the console.
+    pub fn info(&self, message: &str) {
+        self.send(AppAction::Info(message.to_string()));
+    }
+
+    /// Logs a debug-level message on the console.
+    pub fn debug(&self, message: &str) {
+        self.send(AppAction::Debug(message.to_string()));
+    }
+
+    /// Logs a warn-level message on the console.
+    pub fn warn(&self, message: &str) {
+        self.send(AppAction::Warn(message.to_string()));
+    }
+
+    /// Logs an error-level message on the console.
+    pub fn error(&self, message: &str) {
+        self.send(AppAction::Error(message.to_string()));
+    }
+
+    /// Logs a critical-level message on the console.
+    pub fn critical(&self, message: &str) {
+        self.send(AppAction::Critical(message.to_string()));
+    }
+}
+
+/// Closure run by a [`BlockingCommand`].
+type BlockingHandler = std::sync::Arc<dyn Fn(&[&str]) -> String + Send + Sync + 'static>;
+
+/// A sync handler run on a blocking thread with a time limit.
+///
+/// Sync commands normally run on the console loop, so a slow one (e.g. shelling
+/// out to a hanging tool) freezes the console. Registered as an async command, a
+/// `BlockingCommand` runs its closure via `spawn_blocking` instead and fails with
+/// a timeout error once `timeout` elapses, so the console stays responsive.
+///
+/// The closure cannot borrow the `TerminalApp`, since it runs on another thread
+/// while the console keeps going; it only receives the arguments. To change
+/// console state, capture a [`ConsoleHandle`](crate::ConsoleHandle) and send
+/// [`AppAction`](crate::AppAction)s. A blocking thread cannot be interrupted, so
+/// after a timeout or cancellation the closure keeps running until it returns,
+/// and its result is discarded.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::command::{BlockingCommand, execute_command_to_completion};
+/// use daemon_console::{ConsoleOutput, TerminalApp};
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut app = TerminalApp::new();
+///     app.set_output(ConsoleOutput::Sink);
+///     let slow = BlockingCommand::new(Duration::from_millis(20), |_args: &[&str]| {
+///         std::thread::sleep(Duration::from_millis(500));
+///         "done".to_string()
+///     });
+///     app.register_async_command("slow", Box::new(slow));
+///
+///     let output = execute_command_to_completion(&mut app, "slow").await;
+///     assert!(output.contains("timed out"));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct BlockingCommand {
+    handler: BlockingHandler,
+    timeout: Duration,
+}
+
+impl BlockingCommand {
+    /// Wraps a sync closure that may run for at most `timeout`.
+    pub fn new<F>(timeout: Duration, handler: F) -> Self
+    where
+        F: Fn(&[&str]) -> String + Send + Sync + 'static,
+    {
+        Self {
+            handler: std::sync::Arc::new(handler),
+            timeout,
+        }
+    }
+
+    /// Runs the closure on a blocking thread, returning an error message if it
+    /// panicked, timed out or was cancelled.
+    async fn run(&self, args: &[&str], cancel: &CancellationToken) -> Result<String, String> {
+        let handler = self.handler.clone();
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let task = tokio::task::spawn_blocking(move || {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            handler(&args)
+        });
+        tokio::select! {
+            result = tokio::time::timeout(self.timeout, task) => match result {
+                Ok(Ok(output)) => Ok(output),
+                Ok(Err(e)) => Err(get_error!(&format!("Command failed: {}", e), "CommandStatus")),
+                Err(_) => Err(get_error!(
+                    &format!("Command timed out after {:?}", self.timeout),
+                    "CommandStatus"
+                )),
+            },
+            _ = cancel.cancelled() => Err(get_warn!("Command cancelled", "CommandStatus")),
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncCommandHandler for BlockingCommand {
+    async fn execute_async(&mut self, app: &mut TerminalApp, args: &[&str]) -> String {
+        self.execute_cancellable(app, args, CancellationToken::new())
+            .await
+    }
+
+    async fn execute_cancellable(
+        &mut self,
+        app: &mut TerminalApp,
+        args: &[&str],
+        cancel: CancellationToken,
+    ) -> String {
+        self.run(args, &cancel).await.unwrap_or_else(|error| {
+            app.mark_command_failed();
+            error
+        })
+    }
+
+    async fn execute_with_context(&mut self, ctx: &CommandContext, args: &[&str]) -> String {
+        self.run(args, ctx.cancel_token())
+            .await
+            .unwrap_or_else(|error| error)
+    }
+
+    fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+        Box::new(self.clone())
+    }
+}
+
 /// Internal enum to hold either sync or async command handlers.
 pub enum CommandHandlerType {
     PubSync(Box<dyn CommandHandler>),
@@ -61,6 +546,12 @@ pub enum CommandHandlerType {
 pub struct RunningCommand {
     pub command: String,
     pub handle: JoinHandle<String>,
+    /// Whether this is a task started with `spawn_tracked` rather than a command.
+    pub tracked: bool,
+    /// Token handed to the handler for cooperative cancellation.
+    pub cancel_token: CancellationToken,
+    /// When cancellation was requested; the task is aborted after a grace period.
+    pub cancel_requested: Option<Instant>,
 }
 
 // Re-export the variants with expected names inside crate via type aliasing
@@ -70,6 +561,77 @@ impl CommandHandlerType {
     }
 }
 
+/// Conversion into a registrable handler, used by [`register_commands!`](crate::register_commands).
+///
+/// Implemented for sync closures, `Box<dyn CommandHandler>` and
+/// `Box<dyn AsyncCommandHandler>`, so one registration call accepts either kind.
+pub trait IntoCommandHandlerType {
+    fn into_handler_type(self) -> CommandHandlerType;
+}
+
+impl<F> IntoCommandHandlerType for F
+where
+    F: FnMut(&mut TerminalApp, &[&str]) -> String + Send + Sync + 'static,
+{
+    fn into_handler_type(self) -> CommandHandlerType {
+        CommandHandlerType::PubSync(Box::new(self))
+    }
+}
+
+impl IntoCommandHandlerType for Box<dyn CommandHandler> {
+    fn into_handler_type(self) -> CommandHandlerType {
+        CommandHandlerType::PubSync(self)
+    }
+}
+
+impl IntoCommandHandlerType for Box<dyn AsyncCommandHandler> {
+    fn into_handler_type(self) -> CommandHandlerType {
+        CommandHandlerType::PubAsync(self)
+    }
+}
+
+/// Registers several commands at once from `name => handler` pairs.
+///
+/// Each handler may be a sync closure, a `Box<dyn CommandHandler>` or a
+/// `Box<dyn AsyncCommandHandler>`; the matching registration is picked by type.
+///
+/// # Examples
+///
+/// ```
+/// use async_trait::async_trait;
+/// use daemon_console::{AsyncCommandHandler, TerminalApp, get_info, register_commands};
+///
+/// #[derive(Clone)]
+/// struct SleepCommand;
+///
+/// #[async_trait]
+/// impl AsyncCommandHandler for SleepCommand {
+///     async fn execute_async(&mut self, _: &mut TerminalApp, _: &[&str]) -> String {
+///         get_info!("Wake up!")
+///     }
+///
+///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+///         Box::new(self.clone())
+///     }
+/// }
+///
+/// let mut app = TerminalApp::new();
+/// register_commands!(app, {
+///     "hello" => |_: &mut TerminalApp, _: &[&str]| -> String { get_info!("Hello, World!") },
+///     "exit" => |app: &mut TerminalApp, _: &[&str]| -> String {
+///         app.should_exit = true;
+///         String::new()
+///     },
+///     "wait" => Box::new(SleepCommand) as Box<dyn AsyncCommandHandler>,
+/// });
+/// ```
+#[macro_export]
+macro_rules! register_commands {
+    ($app:expr, { $($name:expr => $handler:expr),* $(,)? }) => {{
+        $( $app.register_handler($name, $handler); )*
+    }};
+}
+
 pub type UnknownCommandHandler = Box<dyn Fn(&str) -> String + Send + Sync + 'static>;
 pub type AsyncUnknownCommandHandler =
     Box<dyn Fn(&str) -> BoxFuture<'static, String> + Send + Sync + 'static>;
@@ -84,6 +646,183 @@ where
     }
 }
 
+/// Splits a command line into the command token and the remaining argument text.
+///
+/// Leading whitespace is skipped; the argument text keeps its original spacing.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::command::split_command_name;
+///
+/// assert_eq!(split_command_name("hello  big world"), ("hello", "  big world"));
+/// assert_eq!(split_command_name("help"), ("help", ""));
+/// ```
+pub fn split_command_name(line: &str) -> (&str, &str) {
+    let line = line.trim_start();
+    match line.find(char::is_whitespace) {
+        Some(idx) => line.split_at(idx),
+        None => (line, ""),
+    }
+}
+
+/// Styles a command line with the command name and its arguments in distinct colors.
+///
+/// Returns the line unchanged when colors are disabled.
+pub fn highlight_command_line(line: &str) -> String {
+    if !color_enabled() {
+        return line.to_string();
+    }
+    let (name, args) = split_command_name(line);
+    format!(
+        "{}{}{}{}{}{}{}",
+        SetForegroundColor(Color::Cyan),
+        Attribute::Bold,
+        name,
+        Attribute::Reset,
+        SetForegroundColor(Color::Grey),
+        args,
+        ResetColor
+    )
+}
+
+/// Maximum nesting depth of `$(...)` command substitutions.
+pub const MAX_SUBSTITUTION_DEPTH: usize = 8;
+
+/// Replaces each `$(subcmd args)` in a command line with the output of `subcmd`.
+///
+/// Only sync commands can be substituted, which keeps expansion bounded; nested
+/// substitutions are expanded innermost first, up to [`MAX_SUBSTITUTION_DEPTH`]
+/// levels. A `$(` inside quotes or after a backslash is left as it is. ANSI
+/// styling and trailing newlines are removed from each captured output, and the
+/// output itself is not expanded again.
+///
+/// # Errors
+///
+/// Returns a message for unbalanced parentheses, unknown or async commands, and
+/// nesting beyond the depth limit.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::TerminalApp;
+/// use daemon_console::command::expand_substitutions;
+///
+/// let mut app = TerminalApp::new();
+/// app.register_command(
+///     "whoami",
+///     Box::new(|_: &mut TerminalApp, _: &[&str]| -> String { "operator\n".to_string() }),
+/// );
+///
+/// let line = expand_substitutions(&mut app, "hello $(whoami)").unwrap();
+/// assert_eq!(line, "hello operator");
+/// assert!(expand_substitutions(&mut app, "hello $(whoami").is_err());
+///
+/// // Substituted commands are tokenized like any other command line.
+/// app.register_command(
+///     "count",
+///     Box::new(|_: &mut TerminalApp, args: &[&str]| -> String { args.len().to_string() }),
+/// );
+/// assert_eq!(expand_substitutions(&mut app, r#"$(count "big world")"#).unwrap(), "1");
+///
+/// // Quoted text is not expanded.
+/// let line = expand_substitutions(&mut app, "echo '$(whoami)' \"$(whoami)\"").unwrap();
+/// assert_eq!(line, "echo '$(whoami)' \"$(whoami)\"");
+///
+/// // Styled output, e.g. from `get_info!`, is spliced in as plain text.
+/// app.register_command(
+///     "host",
+///     Box::new(|_: &mut TerminalApp, _: &[&str]| -> String {
+///         daemon_console::get_info!("web-1", "CommandResp")
+///     }),
+/// );
+/// let line = expand_substitutions(&mut app, "ping $(host)").unwrap();
+/// assert!(line.starts_with("ping ") && line.ends_with("web-1"));
+/// assert!(!line.contains('\x1b'));
+/// ```
+pub fn expand_substitutions(app: &mut TerminalApp, line: &str) -> Result<String, String> {
+    expand_substitutions_at(app, line, 0)
+}
+
+fn expand_substitutions_at(
+    app: &mut TerminalApp,
+    line: &str,
+    depth: usize,
+) -> Result<String, String> {
+    if !line.contains("$(") {
+        return Ok(line.to_string());
+    }
+    if depth >= MAX_SUBSTITUTION_DEPTH {
+        return Err(format!(
+            "Command substitution nested deeper than {} levels",
+            MAX_SUBSTITUTION_DEPTH
+        ));
+    }
+
+    let starts: Vec<usize> = crate::parse::unquoted_offsets(line)
+        .into_iter()
+        .filter(|&idx| line[idx..].starts_with("$("))
+        .collect();
+    let mut expanded = String::new();
+    let mut copied = 0;
+    for start in starts {
+        expanded.push_str(&line[copied..start]);
+        let inner_start = start + 2;
+        let mut level = 1;
+        let mut inner_end = None;
+        for (i, c) in line[inner_start..].char_indices() {
+            match c {
+                '(' => level += 1,
+                ')' => {
+                    level -= 1;
+                    if level == 0 {
+                        inner_end = Some(inner_start + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let inner_end = inner_end
+            .ok_or_else(|| "Unbalanced parentheses in command substitution".to_string())?;
+        let inner = expand_substitutions_at(app, &line[inner_start..inner_end], depth + 1)?;
+        let output = crate::utils::strip_ansi(&execute_for_substitution(app, &inner)?);
+        expanded.push_str(output.trim_end_matches(['\n', '\r']));
+        copied = inner_end + 1;
+    }
+    expanded.push_str(&line[copied..]);
+    Ok(expanded)
+}
+
+/// Runs a sync command for `$(...)` substitution and returns its raw output.
+fn execute_for_substitution(app: &mut TerminalApp, line: &str) -> Result<String, String> {
+    let Some(parsed) = crate::parse::ParsedCommand::parse(line) else {
+        return Ok(String::new());
+    };
+    let cmd_name = parsed.name.as_str();
+    let args = parsed.arg_refs();
+    match app.commands.remove(cmd_name) {
+        Some(CommandHandlerType::PubSync(mut sync_handler)) => {
+            let outer = app.current_command.replace(parsed.clone());
+            let result = execute_sync(&mut sync_handler, app, line, &args);
+            app.current_command = outer;
+            app.commands.insert(
+                cmd_name.to_string(),
+                CommandHandlerType::PubSync(sync_handler),
+            );
+            Ok(result)
+        }
+        Some(handler) => {
+            app.commands.insert(cmd_name.to_string(), handler);
+            Err(format!(
+                "Only sync commands can be substituted: '{}'",
+                cmd_name
+            ))
+        }
+        None => Err(format!("Command not found in substitution: '{}'", cmd_name)),
+    }
+}
+
 /// Executes a command by looking it up in the registered commands.
 ///
 /// For sync commands, executes immediately and returns the result.
@@ -98,26 +837,158 @@ where
 ///
 /// String output from the command execution (empty for async commands)
 pub async fn execute_command(app: &mut TerminalApp, command: &str) -> String {
-    let parts: Vec<&str> = command.split_whitespace().collect();
-    if parts.is_empty() {
-        return String::new();
+    app.record_external_history(command);
+    dispatch_command(app, command, false).await
+}
+
+/// Executes a command and waits for it to finish, including async commands.
+///
+/// Unlike [`execute_command`], async handlers are awaited in place against `app`
+/// instead of being spawned in the background, so the returned string is always
+/// the command's final output. Used by one-shot mode.
+///
+/// # Arguments
+///
+/// * `app` - Terminal application
+/// * `command` - Full command string including arguments
+pub async fn execute_command_to_completion(app: &mut TerminalApp, command: &str) -> String {
+    app.record_external_history(command);
+    dispatch_command(app, command, true).await
+}
+
+/// Awaits an async handler in place or spawns it in the background.
+async fn run_async_handler(
+    app: &mut TerminalApp,
+    mut handler: Box<dyn AsyncCommandHandler>,
+    command: &str,
+    cmd_name: &str,
+    args: &[&str],
+    await_async: bool,
+) -> String {
+    if await_async {
+        return handler.execute_async(app, args).await;
+    }
+    let args = args.iter().map(|arg| arg.to_string()).collect();
+    match app
+        .spawn_async_command(command.to_string(), args, handler)
+        .await
+    {
+        Ok(_) if app.quiet_async_commands.contains(cmd_name) => String::new(),
+        Ok(_) => {
+            get_info!(
+                &format!("Async command '{}' started in the background", cmd_name),
+                "CommandStatus"
+            )
+        }
+        Err(e) => {
+            app.mark_command_failed();
+            get_error!(
+                &format!("Failed to spawn async command: {}", e),
+                "CommandStatus"
+            )
+        }
+    }
+}
+
+/// Runs the prefix handler at `idx`, passing the text after the prefix verbatim.
+async fn dispatch_prefix(
+    app: &mut TerminalApp,
+    idx: usize,
+    command: &str,
+    await_async: bool,
+) -> String {
+    let (prefix, handler) = app.prefix_handlers.remove(idx);
+    let rest = &command.trim_start()[prefix.len()..];
+    let args: Vec<&str> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        vec![rest]
+    };
+    app.metrics.commands_executed += 1;
+    match handler {
+        CommandHandlerType::PubSync(mut sync_handler) => {
+            let result = execute_sync(&mut sync_handler, app, command, &args);
+            app.prefix_handlers
+                .insert(idx, (prefix, CommandHandlerType::PubSync(sync_handler)));
+            result
+        }
+        CommandHandlerType::PubAsync(async_handler) => {
+            let cloned_handler = async_handler.box_clone();
+            app.prefix_handlers.insert(
+                idx,
+                (prefix.clone(), CommandHandlerType::PubAsync(async_handler)),
+            );
+            run_async_handler(app, cloned_handler, command, &prefix, &args, await_async).await
+        }
+    }
+}
+
+pub(crate) async fn dispatch_command(
+    app: &mut TerminalApp,
+    command: &str,
+    await_async: bool,
+) -> String {
+    let started = Instant::now();
+    let spawned_before = app.metrics.async_spawned;
+    let output = route_command(app, command, await_async).await;
+    // Commands spawned in the background are recorded when their result arrives
+    if app.metrics.async_spawned == spawned_before && !command.trim().is_empty() {
+        app.record_command_run(command, started.elapsed());
+    }
+    output
+}
+
+/// Finds the handler for a command line and runs it.
+async fn route_command(app: &mut TerminalApp, command: &str, await_async: bool) -> String {
+    app.command_failed = false;
+    if let Some(idx) = app.matching_prefix_handler(command) {
+        return dispatch_prefix(app, idx, command, await_async).await;
     }
 
-    let cmd_name = parts[0];
-    let args = &parts[1..];
+    let Some(mut parsed) = crate::parse::ParsedCommand::parse(command) else {
+        return String::new();
+    };
+    app.metrics.commands_executed += 1;
 
+    if app.fuzzy_exec && !app.commands.contains_key(&parsed.name) {
+        let mut candidates = app.fuzzy_candidates(&parsed.name);
+        match candidates.len() {
+            0 => {}
+            1 => parsed.name = candidates.remove(0),
+            _ => {
+                app.mark_command_failed();
+                return get_warn!(
+                    &format!(
+                        "Ambiguous command '{}', candidates: {}",
+                        parsed.name,
+                        candidates.join(", ")
+                    ),
+                    "CommandStatus"
+                );
+            }
+        }
+    }
+
+    let cmd_name = parsed.name.as_str();
+    let args = parsed.arg_refs();
     if let Some(handler) = app.commands.get(cmd_name) {
         match handler {
             CommandHandlerType::PubSync(_) => {
+                if let Some(output) = app.cached_output(cmd_name, command) {
+                    return output;
+                }
                 // Remove, execute, and put back sync handler
                 if let Some(CommandHandlerType::PubSync(mut sync_handler)) =
                     app.commands.remove(cmd_name)
                 {
-                    let result = sync_handler.execute(app, args);
+                    let outer = app.current_command.replace(parsed.clone());
+                    let result = execute_sync(&mut sync_handler, app, command, &args);
+                    app.current_command = outer;
                     app.commands.insert(
                         cmd_name.to_string(),
                         CommandHandlerType::PubSync(sync_handler),
                     );
+                    app.cache_output(cmd_name, command, &result);
                     result
                 } else {
                     get_error!("Internal error: sync handler not found", "CommandStatus")
@@ -126,23 +997,7 @@ pub async fn execute_command(app: &mut TerminalApp, command: &str) -> String {
             CommandHandlerType::PubAsync(async_handler) => {
                 // Clone the async handler for execution
                 let cloned_handler = async_handler.box_clone();
-                match app
-                    .spawn_async_command(command.to_string(), cloned_handler)
-                    .await
-                {
-                    Ok(_) => {
-                        get_info!(
-                            &format!("Async command '{}' started in the background", cmd_name),
-                            "CommandStatus"
-                        )
-                    }
-                    Err(e) => {
-                        get_error!(
-                            &format!("Failed to spawn async command: {}", e),
-                            "CommandStatus"
-                        )
-                    }
-                }
+                run_async_handler(app, cloned_handler, command, cmd_name, &args, await_async).await
             }
         }
     } else if let Some(ref handler) = app.async_unknown_command_handler {
@@ -150,6 +1005,7 @@ pub async fn execute_command(app: &mut TerminalApp, command: &str) -> String {
     } else if let Some(ref handler) = app.unknown_command_handler {
         handler(command)
     } else {
+        app.mark_command_failed();
         get_warn!(
             &format!("Command not found or registered: '{}'", command),
             "CommandStatus"
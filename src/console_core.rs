@@ -0,0 +1,1764 @@
+//! Headless console state shared by every frontend.
+//!
+//! `ConsoleCore` owns everything that does not touch the terminal: the command
+//! registry, command history, running async commands, the action channel and the
+//! event stream. `TerminalApp` wraps it and adds rendering and key handling, so a
+//! GUI or web frontend can drive the same registry and events without a terminal.
+//!
+//! Command handlers take a `TerminalApp` for compatibility. When a core dispatches
+//! on its own, see [`ConsoleCore::execute_command`], it lends itself to a headless
+//! app for the duration of the call.
+
+use crate::AppAction;
+use crate::ConsoleError;
+use crate::TerminalApp;
+use crate::command::{
+    AsyncCommandHandler, AsyncUnknownCommandHandler, CommandHandler, CommandHandlerType,
+    CommandResult, IntoCommandHandlerType, RunningCommand, UnknownCommandHandler,
+};
+use crate::events::DaemonConsoleEvent;
+use crate::logger::LogLevel;
+use crate::parse::{ParsedCommand, Redirection};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+/// Cloneable handle for sending [`AppAction`]s to a running console.
+///
+/// The underlying channel is unbounded by default. When a capacity is configured
+/// with [`ConsoleCore::set_action_capacity`], the two sending methods trade off
+/// differently once the console falls behind:
+///
+/// - [`send`](Self::send) waits for room, applying backpressure to the caller so
+///   nothing is lost but a log storm slows its producer down.
+/// - [`try_send`](Self::try_send) never waits and hands the action back when the
+///   queue is full, so the caller decides whether to drop it.
+#[derive(Clone, Debug)]
+pub struct ConsoleHandle {
+    inner: ActionTx,
+}
+
+#[derive(Clone, Debug)]
+enum ActionTx {
+    Unbounded(mpsc::UnboundedSender<AppAction>),
+    Bounded(mpsc::Sender<AppAction>),
+}
+
+impl ConsoleHandle {
+    /// Sends an action, waiting for queue capacity in bounded mode.
+    ///
+    /// Returns the action back if the console has shut down.
+    pub async fn send(&self, action: AppAction) -> Result<(), AppAction> {
+        match &self.inner {
+            ActionTx::Unbounded(tx) => tx.send(action).map_err(|e| e.0),
+            ActionTx::Bounded(tx) => tx.send(action).await.map_err(|e| e.0),
+        }
+    }
+
+    /// Sends an action without waiting.
+    ///
+    /// Returns the action back if the queue is full or the console has shut down.
+    pub fn try_send(&self, action: AppAction) -> Result<(), AppAction> {
+        match &self.inner {
+            ActionTx::Unbounded(tx) => tx.send(action).map_err(|e| e.0),
+            ActionTx::Bounded(tx) => tx.try_send(action).map_err(|e| match e {
+                mpsc::error::TrySendError::Full(action) => action,
+                mpsc::error::TrySendError::Closed(action) => action,
+            }),
+        }
+    }
+
+    /// Returns whether the handle sends through a bounded channel.
+    pub fn is_bounded(&self) -> bool {
+        matches!(self.inner, ActionTx::Bounded(_))
+    }
+}
+
+/// Receiving half of the action channel, consumed by the `run` loop.
+#[derive(Debug)]
+pub(crate) enum ActionReceiver {
+    Unbounded(mpsc::UnboundedReceiver<AppAction>),
+    Bounded(mpsc::Receiver<AppAction>),
+}
+
+impl ActionReceiver {
+    /// Takes the next queued action without waiting.
+    pub(crate) fn try_recv(&mut self) -> Option<AppAction> {
+        match self {
+            ActionReceiver::Unbounded(rx) => rx.try_recv().ok(),
+            ActionReceiver::Bounded(rx) => rx.try_recv().ok(),
+        }
+    }
+}
+
+/// Creates an action channel, bounded when `capacity` is given.
+fn action_channel(capacity: Option<usize>) -> (ConsoleHandle, ActionReceiver) {
+    match capacity {
+        Some(capacity) => {
+            let (tx, rx) = mpsc::channel(capacity.max(1));
+            (
+                ConsoleHandle {
+                    inner: ActionTx::Bounded(tx),
+                },
+                ActionReceiver::Bounded(rx),
+            )
+        }
+        None => {
+            let (tx, rx) = mpsc::unbounded_channel();
+            (
+                ConsoleHandle {
+                    inner: ActionTx::Unbounded(tx),
+                },
+                ActionReceiver::Unbounded(rx),
+            )
+        }
+    }
+}
+
+/// Snapshot of the console's activity counters.
+///
+/// - `commands_executed` counts every non-empty line dispatched through
+///   `execute_command`, including unknown commands.
+/// - The `*_logs` counters count calls to the app logging methods per level.
+/// - `async_spawned` counts async commands started in the background;
+///   `async_completed` and `async_failed` count them once they finish, where a
+///   panicked or aborted task counts as failed.
+/// - `events_emitted` counts events passed on to subscribers or a recording.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsoleMetrics {
+    pub commands_executed: u64,
+    pub info_logs: u64,
+    pub warn_logs: u64,
+    pub error_logs: u64,
+    pub debug_logs: u64,
+    pub critical_logs: u64,
+    pub async_spawned: u64,
+    pub async_completed: u64,
+    pub async_failed: u64,
+    pub events_emitted: u64,
+}
+
+impl ConsoleMetrics {
+    /// Counts one log entry at the given level.
+    pub(crate) fn record_log(&mut self, level: LogLevel) {
+        match level {
+            LogLevel::Info => self.info_logs += 1,
+            LogLevel::Warn => self.warn_logs += 1,
+            LogLevel::Error => self.error_logs += 1,
+            LogLevel::Debug => self.debug_logs += 1,
+            LogLevel::Critical => self.critical_logs += 1,
+        }
+    }
+}
+
+/// How long a cancelled async command may keep running before it is aborted.
+pub const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Stats bucket that aggregates every command without a registered handler.
+pub const UNKNOWN_COMMAND_BUCKET: &str = "<unknown>";
+
+/// Execution statistics of one command, see [`ConsoleCore::command_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandStats {
+    /// Number of completed runs.
+    pub count: u64,
+    /// Sum of all run durations.
+    pub total_duration: Duration,
+    /// Timestamp (milliseconds, like event timestamps) of the last completed run.
+    pub last_run: Option<i64>,
+}
+
+impl CommandStats {
+    /// Returns the mean run duration, or zero before the first run.
+    pub fn average_duration(&self) -> Duration {
+        match u32::try_from(self.count) {
+            Ok(0) => Duration::ZERO,
+            Ok(count) => self.total_duration / count,
+            Err(_) => {
+                Duration::from_secs_f64(self.total_duration.as_secs_f64() / self.count as f64)
+            }
+        }
+    }
+}
+
+/// Terminal-independent console state: commands, history and events.
+pub struct ConsoleCore {
+    pub command_history: Vec<String>,
+    pub history_index: Option<usize>,
+    pub(crate) history_prefix: String,
+    pub should_exit: bool,
+    pub(crate) commands: HashMap<String, CommandHandlerType>,
+    pub(crate) prefix_handlers: Vec<(String, CommandHandlerType)>,
+    pub(crate) quiet_async_commands: HashSet<String>,
+    /// Names whose registered command is still the built-in one.
+    builtin_names: HashSet<String>,
+    pub(crate) command_paging: HashMap<String, bool>,
+    command_cache_ttls: HashMap<String, Duration>,
+    command_cache: HashMap<String, (Instant, String)>,
+    pub(crate) fuzzy_exec: bool,
+    pub(crate) external_history: bool,
+    pub(crate) current_command: Option<ParsedCommand>,
+    pub(crate) unknown_command_handler: Option<UnknownCommandHandler>,
+    pub(crate) async_unknown_command_handler: Option<AsyncUnknownCommandHandler>,
+    pub(crate) command_result_rx: Option<mpsc::UnboundedReceiver<CommandResult>>,
+    pub(crate) command_result_tx: Option<mpsc::UnboundedSender<CommandResult>>,
+    pub(crate) running_commands: Vec<RunningCommand>,
+    pub(crate) log_event_suppression: u32,
+    pub(crate) events_paused: bool,
+    pub(crate) last_output: Option<String>,
+    pub(crate) command_failed: bool,
+    pub(crate) command_stats: HashMap<String, CommandStats>,
+    pub(crate) default_module: Option<String>,
+    pub(crate) quiet: bool,
+    pub(crate) quiet_level: LogLevel,
+    pub(crate) current_correlation_id: Option<String>,
+    pub(crate) metrics: ConsoleMetrics,
+    pub(crate) redirections: HashMap<String, Redirection>,
+    pub(crate) results_tx: broadcast::Sender<CommandResult>,
+    pub(crate) recording: Option<BufWriter<File>>,
+    pub(crate) task_runtime: Option<tokio::runtime::Handle>,
+    pub(crate) action_sender: ConsoleHandle,
+    pub(crate) action_receiver: Option<ActionReceiver>,
+    pub events_tx: Option<broadcast::Sender<DaemonConsoleEvent>>,
+}
+
+impl Default for ConsoleCore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsoleCore {
+    /// Creates a new console core with an empty registry and fresh channels.
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (events_tx, _events_rx) = broadcast::channel::<DaemonConsoleEvent>(256);
+        let (action_tx, action_rx) = action_channel(None);
+        Self {
+            command_result_rx: Some(rx),
+            command_result_tx: Some(tx),
+            results_tx: broadcast::channel(256).0,
+            action_receiver: Some(action_rx),
+            events_tx: Some(events_tx),
+            ..Self::detached(Some(action_tx))
+        }
+    }
+
+    /// Creates a core that only sends actions through `handle`.
+    ///
+    /// Without a handle, sent actions are dropped. Events are disabled and there
+    /// is no action loop or command result channel, so async commands cannot be
+    /// started from it. Meant for short-lived cores created per command run, such
+    /// as the one behind the default
+    /// [`execute_with_context`](crate::AsyncCommandHandler::execute_with_context).
+    pub fn detached(handle: Option<ConsoleHandle>) -> Self {
+        Self {
+            command_history: Vec::new(),
+            history_index: None,
+            history_prefix: String::new(),
+            should_exit: false,
+            commands: HashMap::new(),
+            prefix_handlers: Vec::new(),
+            quiet_async_commands: HashSet::new(),
+            builtin_names: HashSet::new(),
+            command_paging: HashMap::new(),
+            command_cache_ttls: HashMap::new(),
+            command_cache: HashMap::new(),
+            fuzzy_exec: false,
+            external_history: false,
+            current_command: None,
+            unknown_command_handler: None,
+            async_unknown_command_handler: None,
+            command_result_rx: None,
+            command_result_tx: None,
+            running_commands: Vec::new(),
+            log_event_suppression: 0,
+            events_paused: false,
+            last_output: None,
+            command_failed: false,
+            command_stats: HashMap::new(),
+            default_module: Some("Stream".into()),
+            quiet: false,
+            quiet_level: LogLevel::Error,
+            current_correlation_id: None,
+            metrics: ConsoleMetrics::default(),
+            redirections: HashMap::new(),
+            results_tx: broadcast::channel(1).0,
+            recording: None,
+            task_runtime: None,
+            action_sender: handle.unwrap_or_else(|| action_channel(None).0),
+            action_receiver: None,
+            events_tx: None,
+        }
+    }
+
+    /// Gets a handle for sending actions to the console from async commands
+    ///
+    /// Works in both bounded and unbounded mode; see [`ConsoleHandle`].
+    pub fn get_action_sender(&self) -> ConsoleHandle {
+        self.action_sender.clone()
+    }
+
+    /// Switches the action channel between unbounded (`None`) and bounded mode.
+    ///
+    /// This replaces the channel, so call it before handing out senders and before
+    /// `run`; handles obtained earlier are disconnected.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Maximum number of queued actions, or `None` for unbounded
+    pub fn set_action_capacity(&mut self, capacity: Option<usize>) {
+        let (action_tx, action_rx) = action_channel(capacity);
+        self.action_sender = action_tx;
+        self.action_receiver = Some(action_rx);
+    }
+
+    /// Stops log calls from dispatching `TerminalLog` events until the matching
+    /// [`resume_log_events`](Self::resume_log_events).
+    ///
+    /// Calls nest: events resume only once every suppression has been released.
+    pub(crate) fn suppress_log_events(&mut self) {
+        self.log_event_suppression += 1;
+    }
+
+    /// Releases one level of [`suppress_log_events`](Self::suppress_log_events).
+    pub(crate) fn resume_log_events(&mut self) {
+        self.log_event_suppression = self.log_event_suppression.saturating_sub(1);
+    }
+
+    /// Enables or disables emitting events to subscribers.
+    ///
+    /// Useful for silencing the event stream during bulk work such as replaying a
+    /// script. This flag is separate from the internal suppression of log events
+    /// for queued log actions, so the two never undo each other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// let mut events = app.subscribe_events().unwrap();
+    ///
+    /// app.set_event_dispatch(false);
+    /// app.info("bulk replay");
+    /// assert!(events.try_recv().is_err());
+    ///
+    /// app.set_event_dispatch(true);
+    /// app.info("back to normal");
+    /// assert!(events.try_recv().is_ok());
+    /// ```
+    pub fn set_event_dispatch(&mut self, enabled: bool) {
+        self.events_paused = !enabled;
+    }
+
+    /// Returns whether events are currently emitted to subscribers.
+    pub fn event_dispatch_enabled(&self) -> bool {
+        !self.events_paused
+    }
+
+    /// Turns the event system on or off entirely.
+    ///
+    /// Unlike [`set_event_dispatch`](Self::set_event_dispatch), which pauses
+    /// dispatch temporarily, disabling drops the event channel: existing
+    /// subscribers see the channel close, [`subscribe_events`](Self::subscribe_events)
+    /// returns `None`, and events are no longer even constructed unless a recording
+    /// is active. Meant for pure terminal use where nobody listens. Re-enabling
+    /// creates a fresh channel. Enabled by default.
+    ///
+    /// Events queued by background tasks as [`AppAction::Emit`] are already built
+    /// when they arrive and are dropped instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    /// use tokio::sync::broadcast::error::TryRecvError;
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// let mut events = app.subscribe_events().unwrap();
+    ///
+    /// app.set_events_enabled(false);
+    /// app.info("nobody is listening");
+    /// assert!(app.subscribe_events().is_none());
+    /// assert!(matches!(events.try_recv(), Err(TryRecvError::Closed)));
+    ///
+    /// app.set_events_enabled(true);
+    /// assert!(app.subscribe_events().is_some());
+    /// ```
+    ///
+    /// Nothing is emitted while disabled, including command lifecycle events:
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_command(
+    ///         "status",
+    ///         Box::new(|_: &mut TerminalApp, _: &[&str]| -> String { "ok".to_string() }),
+    ///     );
+    ///
+    ///     app.set_events_enabled(false);
+    ///     app.current_input = "status".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     app.warn("still nobody listening");
+    ///     assert_eq!(app.metrics().events_emitted, 0);
+    ///
+    ///     app.set_events_enabled(true);
+    ///     app.current_input = "status".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert!(app.metrics().events_emitted > 0);
+    /// }
+    /// ```
+    pub fn set_events_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.events_tx = None;
+        } else if self.events_tx.is_none() {
+            self.events_tx = Some(broadcast::channel(256).0);
+        }
+    }
+
+    /// Returns whether an emitted event would reach a subscriber or recording.
+    pub(crate) fn events_wanted(&self) -> bool {
+        !self.events_paused && (self.events_tx.is_some() || self.recording.is_some())
+    }
+
+    /// Subscribes to daemon console events
+    pub fn subscribe_events(&self) -> Option<broadcast::Receiver<DaemonConsoleEvent>> {
+        self.events_tx.as_ref().map(|tx| tx.subscribe())
+    }
+
+    /// Subscribes to events and calls `handler` with each one on a separate task.
+    ///
+    /// Saves the receive loop of the common case: the handler runs on its own
+    /// Tokio task (the task runtime if one is set), so it must be `Send` and
+    /// should not block. If it falls behind and the channel drops events, the
+    /// missed ones are skipped and handling continues with the oldest retained
+    /// event. The task ends when the event channel closes, e.g. after
+    /// [`set_events_enabled(false)`](Self::set_events_enabled); abort the
+    /// returned handle to stop it earlier. Returns `None` when events are
+    /// disabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::events::DaemonConsoleEvent;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     let logs = Arc::new(AtomicUsize::new(0));
+    ///     let counter = logs.clone();
+    ///     let listener = app
+    ///         .spawn_event_handler(move |event| {
+    ///             if let DaemonConsoleEvent::TerminalLog { .. } = event {
+    ///                 counter.fetch_add(1, Ordering::SeqCst);
+    ///             }
+    ///         })
+    ///         .unwrap();
+    ///
+    ///     app.info("first");
+    ///     app.info("second");
+    ///     app.set_events_enabled(false);
+    ///     listener.await.unwrap();
+    ///     assert_eq!(logs.load(Ordering::SeqCst), 2);
+    /// }
+    /// ```
+    pub fn spawn_event_handler<F>(&self, handler: F) -> Option<tokio::task::JoinHandle<()>>
+    where
+        F: Fn(DaemonConsoleEvent) + Send + 'static,
+    {
+        let mut events = self.subscribe_events()?;
+        Some(self.spawn_task(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => handler(event),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }))
+    }
+
+    /// Subscribes to the results of commands submitted at the prompt.
+    ///
+    /// Every command line entered interactively produces one [`CommandResult`] with
+    /// its full output and the correlation id of its `CommandStarted` event. This is
+    /// a copy of the stream, so it does not compete with the `run` loop consuming
+    /// async results.
+    ///
+    /// Ordering: results arrive in the order the console finishes processing them.
+    /// A sync command's result is published before the next line is read; an async
+    /// command's result is published after its output has been printed and its
+    /// `CommandCompleted` event emitted, so async results may interleave with later
+    /// sync ones. A subscriber that lags more than 256 results behind loses the
+    /// oldest ones and sees `RecvError::Lagged`.
+    pub fn subscribe_command_results(&self) -> broadcast::Receiver<CommandResult> {
+        self.results_tx.subscribe()
+    }
+
+    /// Sends a finished command result to result subscribers.
+    pub(crate) fn publish_command_result(&self, result: CommandResult) {
+        let _ = self.results_tx.send(result);
+    }
+
+    /// Starts appending every emitted event to a file, one event per line.
+    ///
+    /// Events are written in their `Debug` form as they are emitted, so the file
+    /// has the same order subscribers see. Events are not recorded while dispatch
+    /// is disabled. An active recording is flushed and replaced.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConsoleError::Io` if the file cannot be opened.
+    pub fn start_recording<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ConsoleError> {
+        let file = Self::open_recording(path.as_ref())?;
+        if let Some(mut previous) = self.recording.replace(file) {
+            previous.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Flushes and closes the event recording.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConsoleError::NotRecording` if no recording is active, or
+    /// `ConsoleError::Io` if flushing fails.
+    pub fn stop_recording(&mut self) -> Result<(), ConsoleError> {
+        let mut recording = self.recording.take().ok_or(ConsoleError::NotRecording)?;
+        recording.flush()?;
+        Ok(())
+    }
+
+    /// Returns whether emitted events are being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Switches the event recording to a new file without stopping it.
+    ///
+    /// The new file is opened first, so on failure the old recording continues
+    /// untouched. Events are recorded synchronously as they are emitted, so every
+    /// event ends up in exactly one of the two files.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConsoleError::NotRecording` if no recording is active, or
+    /// `ConsoleError::Io` if the new file cannot be opened or the old one flushed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleCore, ConsoleError};
+    /// use daemon_console::events::DaemonConsoleEvent;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("daemon_console_rec_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let (day1, day2) = (dir.join("day1.log"), dir.join("day2.log"));
+    /// let input = |raw: &str| DaemonConsoleEvent::UserConsoleInput {
+    ///     raw: raw.to_string(),
+    ///     timestamp: 0,
+    /// };
+    ///
+    /// let mut core = ConsoleCore::new();
+    /// assert!(matches!(core.rotate_recording(&day2), Err(ConsoleError::NotRecording)));
+    ///
+    /// core.start_recording(&day1).unwrap();
+    /// core.emit_events(input("before"));
+    /// core.rotate_recording(&day2).unwrap();
+    /// core.emit_events(input("after"));
+    /// core.stop_recording().unwrap();
+    ///
+    /// let first = std::fs::read_to_string(&day1).unwrap();
+    /// let second = std::fs::read_to_string(&day2).unwrap();
+    /// assert!(first.contains("before") && !first.contains("after"));
+    /// assert!(second.contains("after") && !second.contains("before"));
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn rotate_recording<P: AsRef<Path>>(&mut self, new_path: P) -> Result<(), ConsoleError> {
+        if self.recording.is_none() {
+            return Err(ConsoleError::NotRecording);
+        }
+        let file = Self::open_recording(new_path.as_ref())?;
+        if let Some(mut previous) = self.recording.replace(file) {
+            previous.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Opens a recording file for appending.
+    fn open_recording(path: &Path) -> std::io::Result<BufWriter<File>> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(BufWriter::new(file))
+    }
+
+    /// Emits an event to the event channel unless dispatch is disabled
+    pub fn emit_events(&mut self, event: DaemonConsoleEvent) {
+        if !self.events_wanted() {
+            return;
+        }
+        self.metrics.events_emitted += 1;
+        if let Some(recording) = &mut self.recording {
+            let _ = writeln!(recording, "{:?}", event);
+        }
+        if let Some(tx) = &self.events_tx {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Dispatches log events unless they are currently suppressed
+    pub(crate) fn dispatch_log_events(
+        &mut self,
+        message: &str,
+        level: LogLevel,
+        module_name: Option<&str>,
+    ) {
+        if self.log_event_suppression == 0 && self.events_wanted() {
+            self.emit_events(DaemonConsoleEvent::TerminalLog {
+                level,
+                message: message.to_string(),
+                module_name: module_name.map(str::to_string),
+                timestamp: DaemonConsoleEvent::now_ts(),
+            });
+        }
+    }
+
+    /// Registers a synchronous command with the console
+    pub fn register_command<S: Into<String>>(&mut self, name: S, handler: Box<dyn CommandHandler>) {
+        let name = name.into();
+        self.builtin_names.remove(&name);
+        self.commands
+            .insert(name, CommandHandlerType::PubSync(handler));
+    }
+
+    /// Registers an asynchronous command with the console
+    pub fn register_async_command<S: Into<String>>(
+        &mut self,
+        name: S,
+        handler: Box<dyn AsyncCommandHandler>,
+    ) {
+        let name = name.into();
+        self.builtin_names.remove(&name);
+        self.commands
+            .insert(name, CommandHandlerType::PubAsync(handler));
+    }
+
+    /// Registers a sync or async command, choosing the kind from the handler type
+    pub fn register_handler<S: Into<String>, H: IntoCommandHandlerType>(
+        &mut self,
+        name: S,
+        handler: H,
+    ) {
+        let name = name.into();
+        self.builtin_names.remove(&name);
+        self.commands.insert(name, handler.into_handler_type());
+    }
+
+    /// Registers a built-in command unless a command of that name already exists.
+    pub(crate) fn register_builtin(&mut self, name: &str, handler: Box<dyn CommandHandler>) {
+        if !self.commands.contains_key(name) {
+            self.commands
+                .insert(name.to_string(), CommandHandlerType::PubSync(handler));
+            self.builtin_names.insert(name.to_string());
+        }
+    }
+
+    /// Executes a command line without a terminal.
+    ///
+    /// The same dispatch as [`command::execute_command`](crate::command::execute_command)
+    /// on a `TerminalApp`: handlers receive a [headless](TerminalApp::headless)
+    /// app wrapped around this core, so logs reach the embedder only through the
+    /// [event stream](Self::subscribe_events) and the output is returned. Async
+    /// commands start in the background; collect them with
+    /// [`process_command_results`](Self::process_command_results). App-level
+    /// settings a handler changes, such as the prompt or log file, do not outlast
+    /// the call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::events::DaemonConsoleEvent;
+    /// use daemon_console::{ConsoleCore, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut core = ConsoleCore::new();
+    ///     let mut events = core.subscribe_events().unwrap();
+    ///     core.register_command(
+    ///         "greet",
+    ///         Box::new(|app: &mut TerminalApp, args: &[&str]| -> String {
+    ///             app.info("greeting");
+    ///             format!("hello {}", args.join(" "))
+    ///         }),
+    ///     );
+    ///
+    ///     assert_eq!(core.execute_command("greet web").await, "hello web");
+    ///     assert!(matches!(
+    ///         events.try_recv(),
+    ///         Ok(DaemonConsoleEvent::TerminalLog { message, .. }) if message == "greeting"
+    ///     ));
+    ///     assert!(core.has_command("greet"));
+    /// }
+    /// ```
+    pub async fn execute_command(&mut self, command: &str) -> String {
+        let mut lent = HeadlessApp::new(self);
+        crate::command::execute_command(lent.app(), command).await
+    }
+
+    /// Executes a command line without a terminal, awaiting async commands.
+    ///
+    /// Like [`execute_command`](Self::execute_command), but async handlers run in
+    /// place as with
+    /// [`command::execute_command_to_completion`](crate::command::execute_command_to_completion).
+    pub async fn execute_command_to_completion(&mut self, command: &str) -> String {
+        let mut lent = HeadlessApp::new(self);
+        crate::command::execute_command_to_completion(lent.app(), command).await
+    }
+
+    /// Collects finished background commands without a terminal.
+    ///
+    /// Emits their `CommandCompleted` events and publishes them to
+    /// [`subscribe_command_results`](Self::subscribe_command_results), like
+    /// [`TerminalApp::process_command_results`] minus the rendering. Returns how
+    /// many results were handled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use daemon_console::{AsyncCommandHandler, ConsoleCore, TerminalApp};
+    ///
+    /// struct Backup;
+    ///
+    /// #[async_trait]
+    /// impl AsyncCommandHandler for Backup {
+    ///     async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+    ///         "backup done".to_string()
+    ///     }
+    ///
+    ///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+    ///         Box::new(Backup)
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut core = ConsoleCore::new();
+    ///     let mut results = core.subscribe_command_results();
+    ///     core.register_async_command("backup", Box::new(Backup));
+    ///     core.execute_command("backup").await;
+    ///
+    ///     while core.process_command_results().await.unwrap() == 0 {
+    ///         tokio::task::yield_now().await;
+    ///     }
+    ///     assert_eq!(results.try_recv().unwrap().output, "backup done");
+    /// }
+    /// ```
+    pub async fn process_command_results(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut lent = HeadlessApp::new(self);
+        lent.app().process_command_results().await
+    }
+
+    /// Applies the actions queued by background tasks without a terminal.
+    ///
+    /// Log actions become `TerminalLog` events; see
+    /// [`TerminalApp::process_pending_actions`]. Returns how many actions were
+    /// applied.
+    pub fn process_pending_actions(&mut self) -> usize {
+        HeadlessApp::new(self).app().process_pending_actions()
+    }
+
+    /// Suppresses the "started in the background" message of an async command.
+    ///
+    /// With `quiet` set, launching the command prints nothing; its output still
+    /// appears once it completes. Applies to command names and prefix handlers.
+    /// Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use daemon_console::{AsyncCommandHandler, ConsoleOutput, TerminalApp, command::execute_command};
+    ///
+    /// #[derive(Clone)]
+    /// struct Backup;
+    ///
+    /// #[async_trait]
+    /// impl AsyncCommandHandler for Backup {
+    ///     async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+    ///         "backup done".to_string()
+    ///     }
+    ///
+    ///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+    ///         Box::new(self.clone())
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_async_command("backup", Box::new(Backup));
+    ///     assert!(!execute_command(&mut app, "backup").await.is_empty());
+    ///
+    ///     app.set_command_quiet_start("backup", true);
+    ///     assert_eq!(execute_command(&mut app, "backup").await, "");
+    /// }
+    /// ```
+    pub fn set_command_quiet_start(&mut self, name: &str, quiet: bool) {
+        if quiet {
+            self.quiet_async_commands.insert(name.to_string());
+        } else {
+            self.quiet_async_commands.remove(name);
+        }
+    }
+
+    /// Caches the output of a sync command for `ttl`, or stops caching it with `None`.
+    ///
+    /// Meant for slow, read-only commands. While a cached entry is fresh, running
+    /// the same command line again returns the stored output without calling the
+    /// handler. Entries are keyed on the whole trimmed line, so `status db` and
+    /// `status web` are cached separately. Only successful runs are cached, and
+    /// only commands given a TTL here; async commands are never cached. Use
+    /// [`clear_command_cache`](Self::clear_command_cache) or the `clearcache`
+    /// built-in to drop cached output early.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::command::execute_command;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     let runs = Arc::new(AtomicUsize::new(0));
+    ///     let counter = runs.clone();
+    ///     app.register_command(
+    ///         "disk",
+    ///         Box::new(move |_: &mut TerminalApp, _: &[&str]| {
+    ///             format!("scan {}", counter.fetch_add(1, Ordering::SeqCst) + 1)
+    ///         }),
+    ///     );
+    ///     app.set_command_cache_ttl("disk", Some(Duration::from_secs(60)));
+    ///
+    ///     assert_eq!(execute_command(&mut app, "disk").await, "scan 1");
+    ///     assert_eq!(execute_command(&mut app, "disk").await, "scan 1");
+    ///     assert_eq!(execute_command(&mut app, "disk /var").await, "scan 2");
+    ///
+    ///     // A zero TTL means every cached entry is already stale
+    ///     app.set_command_cache_ttl("disk", Some(Duration::ZERO));
+    ///     assert_eq!(execute_command(&mut app, "disk").await, "scan 3");
+    ///     assert_eq!(execute_command(&mut app, "disk").await, "scan 4");
+    ///
+    ///     app.set_command_cache_ttl("disk", None);
+    ///     assert_eq!(execute_command(&mut app, "disk").await, "scan 5");
+    ///     assert_eq!(runs.load(Ordering::SeqCst), 5);
+    /// }
+    /// ```
+    pub fn set_command_cache_ttl(&mut self, name: &str, ttl: Option<Duration>) {
+        match ttl {
+            Some(ttl) => {
+                self.command_cache_ttls.insert(name.to_string(), ttl);
+            }
+            None => {
+                self.command_cache_ttls.remove(name);
+                self.command_cache
+                    .retain(|line, _| line.split_whitespace().next() != Some(name));
+            }
+        }
+    }
+
+    /// Drops all cached command output, returning the number of entries removed.
+    pub fn clear_command_cache(&mut self) -> usize {
+        let count = self.command_cache.len();
+        self.command_cache.clear();
+        count
+    }
+
+    /// Returns the cached output of `line` if `name` is cached and the entry is fresh.
+    pub(crate) fn cached_output(&mut self, name: &str, line: &str) -> Option<String> {
+        let ttl = *self.command_cache_ttls.get(name)?;
+        let line = line.trim();
+        match self.command_cache.get(line) {
+            Some((stored, output)) if stored.elapsed() < ttl => Some(output.clone()),
+            Some(_) => {
+                self.command_cache.remove(line);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Stores the output of a successful run of `line` if `name` is cached.
+    pub(crate) fn cache_output(&mut self, name: &str, line: &str, output: &str) {
+        if self.command_failed || !self.command_cache_ttls.contains_key(name) {
+            return;
+        }
+        self.command_cache.insert(
+            line.trim().to_string(),
+            (Instant::now(), output.to_string()),
+        );
+    }
+
+    /// Runs the closest registered command when the typed name has no exact match.
+    ///
+    /// Off by default, because a typo may then run a different command than the
+    /// one intended. When enabled and a name is not registered, commands within a
+    /// small edit distance are looked up: one edit for names of up to three
+    /// characters, two otherwise. A single candidate is executed with the given
+    /// arguments; several candidates fail the command and list them; none falls
+    /// back to the unknown command handler. Prefix handlers are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::command::execute_command;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_command("list", Box::new(|_: &mut TerminalApp, _: &[&str]| "listed".to_string()));
+    ///     app.register_command("lint", Box::new(|_: &mut TerminalApp, _: &[&str]| "linted".to_string()));
+    ///     app.set_fuzzy_exec(true);
+    ///
+    ///     assert_eq!(execute_command(&mut app, "lst").await, "listed");
+    ///
+    ///     let ambiguous = execute_command(&mut app, "lit").await;
+    ///     assert!(ambiguous.contains("lint, list"));
+    ///     assert!(!app.last_command_succeeded());
+    /// }
+    /// ```
+    pub fn set_fuzzy_exec(&mut self, enabled: bool) {
+        self.fuzzy_exec = enabled;
+    }
+
+    /// Returns the registered command names close enough to `name` for fuzzy execution.
+    pub(crate) fn fuzzy_candidates(&self, name: &str) -> Vec<String> {
+        let threshold = if name.chars().count() <= 3 { 1 } else { 2 };
+        let mut candidates: Vec<String> = self
+            .commands
+            .keys()
+            .filter(|command| crate::utils::edit_distance(name, command) <= threshold)
+            .cloned()
+            .collect();
+        candidates.sort();
+        candidates
+    }
+
+    /// Returns the parsed form of the command whose sync handler is running.
+    ///
+    /// Lets a handler see the raw line and the name it was invoked under (which
+    /// differs from the typed name after fuzzy matching) without parsing again.
+    /// `None` outside sync handlers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::command::execute_command;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_command(
+    ///         "echo",
+    ///         Box::new(|app: &mut TerminalApp, _: &[&str]| {
+    ///             app.current_command().map(|cmd| cmd.raw.clone()).unwrap_or_default()
+    ///         }),
+    ///     );
+    ///     assert_eq!(execute_command(&mut app, "echo  'a  b'").await, "echo  'a  b'");
+    ///     assert!(app.current_command().is_none());
+    /// }
+    /// ```
+    pub fn current_command(&self) -> Option<&ParsedCommand> {
+        self.current_command.as_ref()
+    }
+
+    /// Returns whether a command with this exact name is registered.
+    ///
+    /// # Examples
+    ///
+    /// Commands can be registered live from a background task:
+    ///
+    /// ```
+    /// use daemon_console::{AppAction, ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     let handle = app.get_action_sender();
+    ///
+    ///     tokio::spawn(async move {
+    ///         let handler = Box::new(|_app: &mut TerminalApp, _args: &[&str]| "pong".to_string());
+    ///         handle
+    ///             .send(AppAction::RegisterCommand("ping".into(), handler))
+    ///             .await
+    ///             .unwrap();
+    ///     })
+    ///     .await
+    ///     .unwrap();
+    ///
+    ///     assert!(!app.has_command("ping"));
+    ///     app.process_pending_actions();
+    ///     assert!(app.has_command("ping"));
+    /// }
+    /// ```
+    pub fn has_command(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    /// Claims every input line that starts with `prefix`, e.g. `!` for shell escapes.
+    ///
+    /// Prefix handlers are consulted before the exact-name lookup. The handler gets
+    /// the text after the prefix verbatim as its only argument, or no arguments when
+    /// nothing follows the prefix. When several prefixes match, the longest wins.
+    /// Registering the same prefix again replaces its handler.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp, command::execute_command};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_prefix_handler("!", |_app: &mut TerminalApp, args: &[&str]| {
+    ///         format!("shell: {}", args.join(""))
+    ///     });
+    ///     app.register_prefix_handler("!!", |_app: &mut TerminalApp, _args: &[&str]| {
+    ///         "repeat last".to_string()
+    ///     });
+    ///     app.register_handler("list", |_app: &mut TerminalApp, _args: &[&str]| {
+    ///         "a b c".to_string()
+    ///     });
+    ///
+    ///     assert_eq!(execute_command(&mut app, "!ls  -la").await, "shell: ls  -la");
+    ///     assert_eq!(execute_command(&mut app, "!!").await, "repeat last");
+    ///     assert_eq!(execute_command(&mut app, "list").await, "a b c");
+    /// }
+    /// ```
+    pub fn register_prefix_handler<S: Into<String>, H: IntoCommandHandlerType>(
+        &mut self,
+        prefix: S,
+        handler: H,
+    ) {
+        let prefix = prefix.into();
+        let handler = handler.into_handler_type();
+        match self.prefix_handlers.iter_mut().find(|(p, _)| *p == prefix) {
+            Some(entry) => entry.1 = handler,
+            None => self.prefix_handlers.push((prefix, handler)),
+        }
+    }
+
+    /// Returns the index of the longest registered prefix that `line` starts with.
+    pub(crate) fn matching_prefix_handler(&self, line: &str) -> Option<usize> {
+        let line = line.trim_start();
+        self.prefix_handlers
+            .iter()
+            .enumerate()
+            .filter(|(_, (prefix, _))| line.starts_with(prefix.as_str()))
+            .max_by_key(|(_, (prefix, _))| prefix.len())
+            .map(|(idx, _)| idx)
+    }
+
+    /// Registers or removes the optional built-in commands (see [`crate::builtins`]).
+    ///
+    /// Built-ins are off by default. Enabling skips names that already have a
+    /// command, and disabling removes only the commands that are still built-ins,
+    /// so user commands sharing a built-in name are kept either way.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,standalone_crate
+    /// use daemon_console::{ConsoleOutput, TerminalApp, command::execute_command};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_builtin_commands(true);
+    ///
+    ///     execute_command(&mut app, "loglevel info").await;
+    ///     app.debug("Hidden now");
+    ///     assert_eq!(app.metrics().debug_logs, 0);
+    ///
+    ///     execute_command(&mut app, "loglevel debug").await;
+    ///     app.debug("Visible again");
+    ///     assert_eq!(app.metrics().debug_logs, 1);
+    ///
+    ///     // A user command of the same name survives toggling the built-ins.
+    ///     app.register_command(
+    ///         "stats",
+    ///         Box::new(|_: &mut TerminalApp, _: &[&str]| -> String { "mine".to_string() }),
+    ///     );
+    ///     app.set_builtin_commands(false);
+    ///     app.set_builtin_commands(true);
+    ///     assert_eq!(execute_command(&mut app, "stats").await, "mine");
+    ///     app.set_builtin_commands(false);
+    ///     assert_eq!(execute_command(&mut app, "stats").await, "mine");
+    ///     assert!(!app.has_command("loglevel"));
+    /// }
+    /// ```
+    pub fn set_builtin_commands(&mut self, enabled: bool) {
+        if enabled {
+            crate::builtins::register_builtins(self);
+        } else {
+            for name in self.builtin_names.drain() {
+                self.commands.remove(&name);
+            }
+        }
+    }
+
+    /// Sets a custom handler for unknown commands (synchronous).
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - Closure that takes the full command string and returns a response
+    pub fn set_unknown_command_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.unknown_command_handler = Some(Box::new(handler));
+    }
+
+    /// Sets a custom handler for unknown commands (asynchronous).
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - Closure that takes the full command string and returns a future
+    pub fn set_async_unknown_command_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&str) -> BoxFuture<'static, String> + Send + Sync + 'static,
+    {
+        self.async_unknown_command_handler = Some(Box::new(handler));
+    }
+
+    /// Removes the custom unknown command handler.
+    pub fn clear_unknown_command_handler(&mut self) {
+        self.unknown_command_handler = None;
+        self.async_unknown_command_handler = None;
+    }
+
+    /// Records a submitted line in the command history and resets navigation.
+    ///
+    /// Up and Down cycle through the history. If the input line is not empty when
+    /// navigation starts, only entries starting with it are visited, and moving
+    /// past the newest match restores the original input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     for line in ["deploy web", "status", "deploy db", "stats"] {
+    ///         app.push_history(line);
+    ///     }
+    ///     let up = Event::Key(KeyEvent::new(KeyCode::Up, KeyModifiers::NONE));
+    ///     let down = Event::Key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+    ///
+    ///     app.set_input("dep");
+    ///     app.process_event(up.clone()).await.unwrap();
+    ///     assert_eq!(app.current_input, "deploy db");
+    ///     app.process_event(up.clone()).await.unwrap();
+    ///     assert_eq!(app.current_input, "deploy web");
+    ///     app.process_event(up.clone()).await.unwrap();
+    ///     assert_eq!(app.current_input, "deploy web");
+    ///     app.process_event(down.clone()).await.unwrap();
+    ///     assert_eq!(app.current_input, "deploy db");
+    ///     app.process_event(down.clone()).await.unwrap();
+    ///     assert_eq!(app.current_input, "dep");
+    ///
+    ///     app.set_input("");
+    ///     app.process_event(up).await.unwrap();
+    ///     assert_eq!(app.current_input, "stats");
+    /// }
+    /// ```
+    pub fn push_history<S: Into<String>>(&mut self, line: S) {
+        self.command_history.push(line.into());
+        self.history_index = None;
+    }
+
+    /// Removes all history entries and resets navigation.
+    pub fn clear_history(&mut self) {
+        self.command_history.clear();
+        self.history_index = None;
+    }
+
+    /// Finds history entries matching `query`, returning their indices and text,
+    /// oldest first.
+    ///
+    /// By default `query` is a case-insensitive substring. With `regex` it is
+    /// compiled as a regular expression and matched as written, so add `(?i)`
+    /// to ignore case. Indices refer to
+    /// [`command_history`](Self::command_history).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsoleError::InvalidPattern`] if `regex` is set and the query is
+    /// not a valid regular expression, or the crate was built without the `regex`
+    /// feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::ConsoleCore;
+    ///
+    /// let mut core = ConsoleCore::new();
+    /// for line in ["deploy web", "status", "Deploy db", "logs web"] {
+    ///     core.push_history(line);
+    /// }
+    ///
+    /// let found = core.search_history("DEPLOY", false).unwrap();
+    /// assert_eq!(found, [(0, "deploy web"), (2, "Deploy db")]);
+    ///
+    /// #[cfg(feature = "regex")]
+    /// {
+    ///     let found = core.search_history(r"^\w+ web$", true).unwrap();
+    ///     assert_eq!(found, [(0, "deploy web"), (3, "logs web")]);
+    ///     assert!(core.search_history("deploy (", true).is_err());
+    /// }
+    /// ```
+    pub fn search_history(
+        &self,
+        query: &str,
+        regex: bool,
+    ) -> Result<Vec<(usize, &str)>, ConsoleError> {
+        let entries = self.command_history.iter().map(String::as_str).enumerate();
+        if !regex {
+            let query = query.to_lowercase();
+            return Ok(entries
+                .filter(|(_, line)| line.to_lowercase().contains(&query))
+                .collect());
+        }
+        #[cfg(feature = "regex")]
+        {
+            let pattern = regex::Regex::new(query)
+                .map_err(|e| ConsoleError::InvalidPattern(e.to_string()))?;
+            Ok(entries.filter(|(_, line)| pattern.is_match(line)).collect())
+        }
+        #[cfg(not(feature = "regex"))]
+        {
+            let _ = entries;
+            Err(ConsoleError::InvalidPattern(
+                "regex queries need the `regex` feature".to_string(),
+            ))
+        }
+    }
+
+    /// Writes the command history to a file, oldest entry first, one per line.
+    ///
+    /// The file is replaced. An empty history produces an empty file. Returns the
+    /// number of entries written.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConsoleError::Io` if the file cannot be written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::TerminalApp;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("daemon_console_history_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let path = dir.join("history.txt");
+    /// let mut app = TerminalApp::new();
+    /// app.push_history("status");
+    /// app.push_history("deploy \"web app\"");
+    /// assert_eq!(app.export_history(&path).unwrap(), 2);
+    ///
+    /// app.clear_history();
+    /// assert_eq!(app.import_history(&path).unwrap(), 2);
+    /// assert_eq!(app.command_history, ["status", "deploy \"web app\""]);
+    ///
+    /// assert!(app.export_history(dir.join("missing/history.txt")).is_err());
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn export_history<P: AsRef<Path>>(&self, path: P) -> Result<usize, ConsoleError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for entry in &self.command_history {
+            writeln!(file, "{}", entry)?;
+        }
+        file.flush()?;
+        Ok(self.command_history.len())
+    }
+
+    /// Appends the entries of a file written by [`export_history`](Self::export_history).
+    ///
+    /// Blank lines are skipped. Returns the number of entries added.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConsoleError::Io` if the file cannot be read.
+    pub fn import_history<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, ConsoleError> {
+        let contents = std::fs::read_to_string(path)?;
+        let before = self.command_history.len();
+        self.command_history.extend(
+            contents
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string),
+        );
+        self.history_index = None;
+        Ok(self.command_history.len() - before)
+    }
+
+    /// Records commands run through `execute_command` and
+    /// `execute_command_to_completion` in the history.
+    ///
+    /// Lines submitted at the prompt are always recorded; this covers commands run
+    /// programmatically, e.g. from automation or one-shot mode, so they can be
+    /// recalled with the Up key as well. Off by default to keep automation out of
+    /// the history.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::command::execute_command;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_history_for_external_commands(true);
+    ///     app.register_command("status", Box::new(|_: &mut TerminalApp, _: &[&str]| "ok".to_string()));
+    ///
+    ///     execute_command(&mut app, "status").await;
+    ///     let up = KeyEvent::new(KeyCode::Up, KeyModifiers::NONE);
+    ///     app.process_event(Event::Key(up)).await.unwrap();
+    ///     assert_eq!(app.current_input, "status");
+    /// }
+    /// ```
+    pub fn set_history_for_external_commands(&mut self, enabled: bool) {
+        self.external_history = enabled;
+    }
+
+    /// Records a programmatically run command if external history is enabled.
+    pub(crate) fn record_external_history(&mut self, line: &str) {
+        if self.external_history && !line.trim().is_empty() {
+            self.push_history(line);
+        }
+    }
+
+    /// Sets the module name used by the simple logging methods (`info`, `warn`, ...).
+    ///
+    /// Defaults to `"Stream"`. Passing `None` logs without a module prefix.
+    /// The name is also reported in the `TerminalLog` events those methods dispatch.
+    pub fn set_default_module(&mut self, module: Option<&str>) {
+        self.default_module = module.map(str::to_string);
+    }
+
+    /// Returns the module name used by the simple logging methods.
+    pub fn default_module(&self) -> Option<&str> {
+        self.default_module.as_deref()
+    }
+
+    /// Hides low-severity log entries from the terminal while enabled.
+    ///
+    /// A quick operator switch for background-ish operation: entries below the
+    /// [quiet level](Self::set_quiet_level) (`Error` by default) are not printed,
+    /// but still reach the log file, metrics and event subscribers. Unlike
+    /// [`logger::set_min_level`](crate::logger::set_min_level), it only affects
+    /// rendering. Off by default; the built-in `quiet` and `verbose` commands
+    /// toggle it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// let captured = OutputBuffer::new();
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    /// app.set_quiet(true);
+    /// app.info("Routine heartbeat");
+    /// app.error("Disk full");
+    ///
+    /// let text = captured.contents();
+    /// assert!(!text.contains("Routine heartbeat"));
+    /// assert!(text.contains("Disk full"));
+    /// assert_eq!(app.metrics().info_logs, 1);
+    /// ```
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    /// Returns whether quiet mode is enabled.
+    pub fn is_quiet(&self) -> bool {
+        self.quiet
+    }
+
+    /// Sets the least severe level still printed in quiet mode.
+    ///
+    /// Use `LogLevel::Warn` to keep warnings visible as well.
+    pub fn set_quiet_level(&mut self, level: LogLevel) {
+        self.quiet_level = level;
+    }
+
+    /// Returns whether quiet mode hides entries of this level from the terminal.
+    pub(crate) fn quiet_hides(&self, level: LogLevel) -> bool {
+        self.quiet && level.severity() < self.quiet_level.severity()
+    }
+
+    /// Returns the output of the most recently completed command.
+    ///
+    /// Every command submitted from the input line replaces this value, including
+    /// commands that printed nothing, which leave an empty string. For async commands
+    /// the slot first holds the "started in the background" notice and is replaced by
+    /// the real output once the result arrives. `None` means no command has run yet.
+    pub fn last_output(&self) -> Option<&str> {
+        self.last_output.as_deref()
+    }
+
+    /// Marks the command currently being executed as failed.
+    ///
+    /// Handlers call this to report failure, which stops `&&` chains. Commands
+    /// that are not found are marked failed automatically.
+    pub fn mark_command_failed(&mut self) {
+        self.command_failed = true;
+    }
+
+    /// Returns whether the most recently executed command succeeded.
+    ///
+    /// An async command counts as successful once it has been started.
+    pub fn last_command_succeeded(&self) -> bool {
+        !self.command_failed
+    }
+
+    /// Returns whether an async command with the given name is still running.
+    ///
+    /// The name is matched against the first whitespace-separated token of each
+    /// running command line, the same token used to look up the handler, so
+    /// `is_running("wait")` is true while `wait 5` runs.
+    pub fn is_running(&self, name: &str) -> bool {
+        self.running_commands.iter().any(|cmd| {
+            !cmd.handle.is_finished() && cmd.command.split_whitespace().next() == Some(name)
+        })
+    }
+
+    /// Returns a snapshot of the activity counters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// app.info("Started");
+    /// app.warn("Disk almost full");
+    /// assert_eq!(app.metrics().info_logs, 1);
+    /// assert_eq!(app.metrics().warn_logs, 1);
+    ///
+    /// app.reset_metrics();
+    /// assert_eq!(app.metrics().info_logs, 0);
+    /// ```
+    pub fn metrics(&self) -> ConsoleMetrics {
+        self.metrics.clone()
+    }
+
+    /// Resets all activity counters to zero.
+    pub fn reset_metrics(&mut self) {
+        self.metrics = ConsoleMetrics::default();
+    }
+
+    /// Returns per-command execution statistics keyed by command name.
+    ///
+    /// Only registered commands get their own entry, keyed by name (or by prefix for
+    /// prefix handlers), which keeps the map bounded. Everything else, including
+    /// input handled by the unknown-command handler, is aggregated under
+    /// [`UNKNOWN_COMMAND_BUCKET`]. Async commands started in the background are
+    /// recorded when their result arrives.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp, command::execute_command};
+    /// use daemon_console::console_core::UNKNOWN_COMMAND_BUCKET;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_handler("ping", |_app: &mut TerminalApp, _args: &[&str]| "pong".to_string());
+    ///
+    ///     execute_command(&mut app, "ping").await;
+    ///     execute_command(&mut app, "ping -c 1").await;
+    ///     execute_command(&mut app, "nope").await;
+    ///
+    ///     assert_eq!(app.command_stats()["ping"].count, 2);
+    ///     assert_eq!(app.command_stats()[UNKNOWN_COMMAND_BUCKET].count, 1);
+    /// }
+    /// ```
+    pub fn command_stats(&self) -> &HashMap<String, CommandStats> {
+        &self.command_stats
+    }
+
+    /// Clears all per-command statistics.
+    pub fn reset_command_stats(&mut self) {
+        self.command_stats.clear();
+    }
+
+    /// Records one completed run of a command line.
+    pub(crate) fn record_command_run(&mut self, command_line: &str, duration: Duration) {
+        let key = match self.matching_prefix_handler(command_line) {
+            Some(idx) => self.prefix_handlers[idx].0.clone(),
+            None => {
+                let (name, _) = crate::command::split_command_name(command_line);
+                if self.commands.contains_key(name) {
+                    name.to_string()
+                } else {
+                    UNKNOWN_COMMAND_BUCKET.to_string()
+                }
+            }
+        };
+        let stats = self.command_stats.entry(key).or_default();
+        stats.count += 1;
+        stats.total_duration += duration;
+        stats.last_run = Some(DaemonConsoleEvent::now_ts());
+    }
+
+    /// Runs async commands and tracked tasks on the given runtime instead of the
+    /// current one.
+    ///
+    /// By default tasks go through `tokio::spawn` and can outlive an embedded
+    /// console. With a dedicated runtime the embedder owns every task the console
+    /// starts: shutting that runtime down cancels them all. `None` restores the
+    /// default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::ConsoleCore;
+    ///
+    /// let commands = tokio::runtime::Runtime::new().unwrap();
+    /// let mut core = ConsoleCore::new();
+    /// core.set_task_runtime(Some(commands.handle().clone()));
+    ///
+    /// // No runtime is entered here; the task runs on `commands`
+    /// core.spawn_tracked("sync-cache", std::future::pending::<String>());
+    /// assert_eq!(core.running_jobs(), ["sync-cache"]);
+    ///
+    /// commands.shutdown_background();
+    /// ```
+    pub fn set_task_runtime(&mut self, runtime: Option<tokio::runtime::Handle>) {
+        self.task_runtime = runtime;
+    }
+
+    /// Spawns a future on the task runtime, or the current runtime by default.
+    pub(crate) fn spawn_task<F>(&self, fut: F) -> tokio::task::JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.task_runtime {
+            Some(runtime) => runtime.spawn(fut),
+            None => tokio::spawn(fut),
+        }
+    }
+
+    /// Spawns a background task that the console tracks like an async command.
+    ///
+    /// The task is listed by [`running_jobs`](Self::running_jobs), counted in the
+    /// async metrics, and aborted when the terminal shuts down. When it finishes, a
+    /// [`DaemonConsoleEvent::TaskFinished`] event carries its output. Must be called
+    /// from within a Tokio runtime unless a task runtime is set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::ConsoleCore;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut core = ConsoleCore::new();
+    ///     core.spawn_tracked("sync-cache", async {
+    ///         tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+    ///         String::new()
+    ///     });
+    ///     assert_eq!(core.running_jobs(), vec!["sync-cache".to_string()]);
+    ///
+    ///     core.abort_running_commands();
+    ///     assert!(core.running_jobs().is_empty());
+    /// }
+    /// ```
+    pub fn spawn_tracked<F>(&mut self, name: &str, fut: F)
+    where
+        F: Future<Output = String> + Send + 'static,
+    {
+        self.running_commands.push(RunningCommand {
+            command: name.to_string(),
+            handle: self.spawn_task(fut),
+            tracked: true,
+            cancel_token: CancellationToken::new(),
+            cancel_requested: None,
+        });
+        self.metrics.async_spawned += 1;
+    }
+
+    /// Returns the command lines and task names that are still running.
+    pub fn running_jobs(&self) -> Vec<String> {
+        self.running_commands
+            .iter()
+            .filter(|cmd| !cmd.handle.is_finished())
+            .map(|cmd| cmd.command.clone())
+            .collect()
+    }
+
+    /// Returns whether an async command is still running, ignoring tracked tasks.
+    pub(crate) fn has_running_commands(&self) -> bool {
+        self.running_commands
+            .iter()
+            .any(|cmd| !cmd.tracked && !cmd.handle.is_finished())
+    }
+
+    /// Aborts every running async command and tracked task immediately.
+    pub fn abort_running_commands(&mut self) {
+        for cmd in self.running_commands.drain(..) {
+            cmd.cancel_token.cancel();
+            cmd.handle.abort();
+        }
+    }
+
+    /// Cancels running async commands whose name (first token) is `name`.
+    ///
+    /// Cancellation is cooperative: each command's token is cancelled so handlers
+    /// overriding [`AsyncCommandHandler::execute_cancellable`] can clean up and
+    /// return; commands still running after [`CANCEL_GRACE_PERIOD`] are aborted.
+    /// Returns the number of commands cancelled.
+    pub fn cancel_command(&mut self, name: &str) -> usize {
+        self.request_cancel(|cmd| cmd.command.split_whitespace().next() == Some(name))
+    }
+
+    /// Cancels matching unfinished commands, leaving tracked tasks alone.
+    fn request_cancel(&mut self, matches: impl Fn(&RunningCommand) -> bool) -> usize {
+        let mut cancelled = 0;
+        for cmd in self.running_commands.iter_mut() {
+            if cmd.tracked || cmd.handle.is_finished() || cmd.cancel_requested.is_some() {
+                continue;
+            }
+            if matches(cmd) {
+                cmd.cancel_token.cancel();
+                cmd.cancel_requested = Some(Instant::now());
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+
+    /// Cancels all running async commands, leaving tracked tasks alone.
+    ///
+    /// Works like [`cancel_command`](Self::cancel_command) for every command.
+    /// Commands that end up aborted deliver no result and no `CommandCompleted`
+    /// event. Returns the number of commands that were still running.
+    pub fn cancel_running_commands(&mut self) -> usize {
+        self.request_cancel(|_| true)
+    }
+
+    /// Removes finished async commands and tracked tasks from the running list.
+    ///
+    /// Emits [`DaemonConsoleEvent::TaskFinished`] for each finished tracked task.
+    pub(crate) fn check_running_commands(&mut self) {
+        let metrics = &mut self.metrics;
+        let mut finished_tasks = Vec::new();
+        self.running_commands.retain_mut(|cmd| {
+            if !cmd.handle.is_finished() {
+                if cmd
+                    .cancel_requested
+                    .is_some_and(|at| at.elapsed() >= CANCEL_GRACE_PERIOD)
+                {
+                    cmd.handle.abort();
+                }
+                return true;
+            }
+            let output = match (&mut cmd.handle).now_or_never() {
+                Some(Ok(output)) => {
+                    metrics.async_completed += 1;
+                    Some(output)
+                }
+                _ => {
+                    metrics.async_failed += 1;
+                    None
+                }
+            };
+            if cmd.tracked {
+                finished_tasks.push((std::mem::take(&mut cmd.command), output));
+            }
+            false
+        });
+        if !self.events_wanted() {
+            return;
+        }
+        for (name, output) in finished_tasks {
+            self.emit_events(DaemonConsoleEvent::TaskFinished {
+                name,
+                failed: output.is_none(),
+                output: output.unwrap_or_default(),
+                timestamp: DaemonConsoleEvent::now_ts(),
+            });
+        }
+    }
+}
+
+/// Lends a core to a headless [`TerminalApp`] and takes it back on drop, even
+/// when the future running a command is cancelled.
+struct HeadlessApp<'a> {
+    core: &'a mut ConsoleCore,
+    app: Option<TerminalApp>,
+}
+
+impl<'a> HeadlessApp<'a> {
+    fn new(core: &'a mut ConsoleCore) -> Self {
+        let lent = std::mem::replace(core, ConsoleCore::detached(None));
+        Self {
+            core,
+            app: Some(TerminalApp::headless(lent)),
+        }
+    }
+
+    fn app(&mut self) -> &mut TerminalApp {
+        self.app.as_mut().expect("the app is only taken on drop")
+    }
+}
+
+impl Drop for HeadlessApp<'_> {
+    fn drop(&mut self) {
+        if let Some(app) = self.app.take() {
+            *self.core = app.into_core();
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Error type for console operations.
+
+use std::fmt;
+
+/// Errors returned by console operations.
+#[derive(Debug)]
+pub enum ConsoleError {
+    /// Writing to or configuring the terminal failed.
+    Io(std::io::Error),
+    /// The given command line contained no command.
+    EmptyCommand,
+    /// An event recording operation was requested while not recording.
+    NotRecording,
+    /// A search pattern could not be used; the string says why.
+    InvalidPattern(String),
+}
+
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsoleError::Io(e) => write!(f, "terminal I/O error: {}", e),
+            ConsoleError::EmptyCommand => write!(f, "no command given"),
+            ConsoleError::NotRecording => write!(f, "events are not being recorded"),
+            ConsoleError::InvalidPattern(reason) => write!(f, "invalid pattern: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConsoleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConsoleError::Io(e) => Some(e),
+            ConsoleError::EmptyCommand
+            | ConsoleError::NotRecording
+            | ConsoleError::InvalidPattern(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ConsoleError {
+    fn from(e: std::io::Error) -> Self {
+        ConsoleError::Io(e)
+    }
+}
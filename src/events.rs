@@ -1,7 +1,10 @@
 use crate::logger::LogLevel;
 use chrono::Local;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum DaemonConsoleEvent {
     UserConsoleInput {
         raw: String,    // raw input
@@ -18,10 +21,80 @@ pub enum DaemonConsoleEvent {
         message: String,
         timestamp: i64,
     },
+    /// A child process started with `spawn_subprocess` is running.
+    SubprocessStarted {
+        pid: u32,
+        command: String,
+        timestamp: i64,
+    },
+    /// A child process exited; `status` is its exit code when known.
+    SubprocessExited {
+        pid: u32,
+        status: Option<i32>,
+        timestamp: i64,
+    },
+    /// A submitted command line is about to be dispatched.
+    CommandStarted {
+        command: String,
+        correlation_id: String,
+        timestamp: i64,
+    },
+    /// A command finished; for async commands this fires when the result arrives.
+    CommandCompleted {
+        command: String,
+        output: String,
+        correlation_id: String,
+        timestamp: i64,
+    },
+    /// A sync command returned structured output; it was rendered as text for
+    /// the terminal. `correlation_id` is set for commands submitted at the prompt.
+    #[cfg(feature = "serde")]
+    CommandOutput {
+        command: String,
+        value: serde_json::Value,
+        correlation_id: Option<String>,
+        timestamp: i64,
+    },
+    /// A task started with `spawn_tracked` finished.
+    ///
+    /// `failed` is true when the task panicked or was aborted; `output` is then empty.
+    TaskFinished {
+        name: String,
+        output: String,
+        failed: bool,
+        timestamp: i64,
+    },
+    /// The interactive loop is alive; sent periodically once a heartbeat
+    /// interval is set.
+    Heartbeat { timestamp: i64 },
+    /// The interactive loop stopped and the terminal is about to be restored.
+    Shutdown {
+        reason: ShutdownReason,
+        timestamp: i64,
+    },
+}
+
+/// Why the interactive loop stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ShutdownReason {
+    /// The user pressed the exit key combination (Ctrl+C twice or Ctrl+D).
+    User,
+    /// `should_exit` was set, e.g. by a command.
+    Requested,
+    /// No key or mouse input arrived within the configured idle timeout.
+    Idle,
 }
 
 impl DaemonConsoleEvent {
     pub fn now_ts() -> i64 {
         Local::now().timestamp_millis()
     }
+
+    /// Generates a short random id used to correlate the events of one command.
+    pub fn new_correlation_id() -> String {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_i64(Self::now_ts());
+        format!("{:08x}", hasher.finish() as u32)
+    }
 }
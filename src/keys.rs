@@ -0,0 +1,186 @@
+//! Keybindings of the input line editor.
+//!
+//! Every editing key is bound to a [`KeyAction`]. The defaults match the
+//! classic bindings (Up/Down for history, Ctrl+Left/Right to jump words, Ctrl+W
+//! to delete a word, ...) and can be changed with
+//! [`TerminalApp::set_keybinding`]. Printable characters are always inserted
+//! unless they are bound; keys without a binding go to the
+//! [unhandled key handler](TerminalApp::set_unhandled_key_handler).
+
+use crate::TerminalApp;
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::fmt;
+
+/// A key combination, displayed like `Ctrl+D` or `Alt+Shift+F5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Creates a binding for `code` pressed together with `modifiers`.
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self { code, modifiers }
+    }
+
+    /// Creates a binding for `code` without modifiers.
+    pub const fn key(code: KeyCode) -> Self {
+        Self::new(code, KeyModifiers::NONE)
+    }
+
+    /// Creates a binding for Ctrl plus `code`.
+    pub const fn ctrl(code: KeyCode) -> Self {
+        Self::new(code, KeyModifiers::CONTROL)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl"),
+            (KeyModifiers::ALT, "Alt"),
+            (KeyModifiers::SHIFT, "Shift"),
+            (KeyModifiers::SUPER, "Super"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(c) if !self.modifiers.is_empty() => {
+                write!(f, "{}", c.to_ascii_uppercase())
+            }
+            code => write!(f, "{}", code),
+        }
+    }
+}
+
+/// An editor action a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    /// Submits the input line.
+    Submit,
+    /// Exits the console (Ctrl+D).
+    Exit,
+    /// Cancels commands, clears the input or exits on a second press (Ctrl+C).
+    Interrupt,
+    /// Shows the previous history entry.
+    HistoryPrevious,
+    /// Shows the next history entry.
+    HistoryNext,
+    /// Moves the cursor one character left.
+    CursorLeft,
+    /// Moves the cursor one character right.
+    CursorRight,
+    /// Moves the cursor to the start of the previous word.
+    WordLeft,
+    /// Moves the cursor to the end of the next word.
+    WordRight,
+    /// Deletes the character before the cursor.
+    DeleteBackward,
+    /// Deletes the word before the cursor.
+    DeleteWord,
+}
+
+impl KeyAction {
+    /// Returns a short description of the action.
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyAction::Submit => "submit the input line",
+            KeyAction::Exit => "exit the console",
+            KeyAction::Interrupt => "cancel commands, clear input, or exit when pressed twice",
+            KeyAction::HistoryPrevious => "previous history entry",
+            KeyAction::HistoryNext => "next history entry",
+            KeyAction::CursorLeft => "move cursor left",
+            KeyAction::CursorRight => "move cursor right",
+            KeyAction::WordLeft => "move to previous word",
+            KeyAction::WordRight => "move to next word",
+            KeyAction::DeleteBackward => "delete character before cursor",
+            KeyAction::DeleteWord => "delete word before cursor",
+        }
+    }
+}
+
+/// The bindings a new console starts with.
+pub(crate) fn default_keybindings() -> Vec<(KeyBinding, KeyAction)> {
+    vec![
+        (KeyBinding::key(KeyCode::Enter), KeyAction::Submit),
+        (KeyBinding::ctrl(KeyCode::Char('d')), KeyAction::Exit),
+        (KeyBinding::ctrl(KeyCode::Char('c')), KeyAction::Interrupt),
+        (KeyBinding::key(KeyCode::Up), KeyAction::HistoryPrevious),
+        (KeyBinding::key(KeyCode::Down), KeyAction::HistoryNext),
+        (KeyBinding::key(KeyCode::Left), KeyAction::CursorLeft),
+        (KeyBinding::key(KeyCode::Right), KeyAction::CursorRight),
+        (KeyBinding::ctrl(KeyCode::Left), KeyAction::WordLeft),
+        (KeyBinding::ctrl(KeyCode::Right), KeyAction::WordRight),
+        (
+            KeyBinding::key(KeyCode::Backspace),
+            KeyAction::DeleteBackward,
+        ),
+        (KeyBinding::ctrl(KeyCode::Char('w')), KeyAction::DeleteWord),
+    ]
+}
+
+impl TerminalApp {
+    /// Binds `key` to `action`, replacing any previous binding of the key.
+    ///
+    /// Other keys bound to the same action keep working, so an action can have
+    /// several keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::keys::{KeyAction, KeyBinding};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_keybinding(KeyBinding::ctrl(KeyCode::Char('q')), KeyAction::Exit);
+    ///
+    ///     let ctrl_q = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL);
+    ///     assert!(app.process_event(Event::Key(ctrl_q)).await.unwrap());
+    ///     assert!(app.keybindings().contains(&(KeyBinding::ctrl(KeyCode::Char('q')), KeyAction::Exit)));
+    /// }
+    /// ```
+    pub fn set_keybinding(&mut self, key: KeyBinding, action: KeyAction) {
+        match self.keybindings.iter_mut().find(|(bound, _)| *bound == key) {
+            Some(binding) => binding.1 = action,
+            None => self.keybindings.push((key, action)),
+        }
+    }
+
+    /// Unbinds `key`, returning the action it was bound to.
+    pub fn remove_keybinding(&mut self, key: KeyBinding) -> Option<KeyAction> {
+        let index = self
+            .keybindings
+            .iter()
+            .position(|(bound, _)| *bound == key)?;
+        Some(self.keybindings.remove(index).1)
+    }
+
+    /// Returns the current `(key, action)` mappings, in the order they were bound.
+    pub fn keybindings(&self) -> &[(KeyBinding, KeyAction)] {
+        &self.keybindings
+    }
+
+    /// Returns the action bound to a pressed key.
+    ///
+    /// Keys other than characters fall back to their unmodified binding, so
+    /// e.g. Shift+Enter still submits.
+    pub(crate) fn key_action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<KeyAction> {
+        let find = |key: KeyBinding| {
+            self.keybindings
+                .iter()
+                .find(|(bound, _)| *bound == key)
+                .map(|(_, action)| *action)
+        };
+        find(KeyBinding::new(code, modifiers)).or_else(|| match code {
+            KeyCode::Char(_) => None,
+            _ => find(KeyBinding::key(code)),
+        })
+    }
+}
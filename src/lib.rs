@@ -22,37 +22,58 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+pub mod args;
+pub mod builtins;
 pub mod command;
+pub mod console_core;
+pub mod error;
 pub mod events;
+pub mod keys;
 pub mod logger;
+pub mod output;
+pub mod pager;
+pub mod parse;
+pub mod plugin;
+pub mod prompt;
+pub mod subprocess;
 pub mod utils;
 
 use crossterm::{
-    cursor,
+    cursor::{self, SetCursorStyle},
     event::{
         self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
         KeyModifiers, poll,
     },
-    execute,
-    terminal::{Clear, ClearType, disable_raw_mode, enable_raw_mode},
+    execute, queue,
+    terminal::{self, Clear, ClearType, disable_raw_mode, enable_raw_mode},
 };
-use futures::future::BoxFuture;
-use std::collections::HashMap;
-use std::io::{Stdout, Write, stdout};
-use std::time::Instant;
-use tokio::sync::{broadcast, mpsc};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use unicode_width::UnicodeWidthChar;
 
 pub use crate::command::{
     AsyncCommandHandler, AsyncUnknownCommandHandler, CommandHandler, CommandHandlerType,
-    CommandResult, RunningCommand, UnknownCommandHandler,
+    CommandResult, IntoCommandHandlerType, RunningCommand, UnknownCommandHandler,
 };
+pub use crate::console_core::{CommandStats, ConsoleCore, ConsoleHandle, ConsoleMetrics};
+pub use crate::error::ConsoleError;
 use crate::logger::LogLevel;
+pub use crate::output::{ConsoleOutput, OutputBuffer};
 
 /// Actions that can be sent from async commands to the main application
 pub enum AppAction {
     /// Register a new command: (command_name, handler)
     RegisterCommand(String, Box<dyn CommandHandler>),
+    /// Register a new async command: (command_name, handler)
+    RegisterAsyncCommand(String, Box<dyn AsyncCommandHandler>),
     /// Log an info message
     Info(String),
     /// Log a debug message
@@ -65,6 +86,8 @@ pub enum AppAction {
     Critical(String),
     /// Unified logger to log any message
     Logger(LogLevel, String, Option<String>, Option<bool>),
+    /// Emit an event to subscribers
+    Emit(events::DaemonConsoleEvent),
 }
 
 impl std::fmt::Debug for AppAction {
@@ -75,11 +98,17 @@ impl std::fmt::Debug for AppAction {
                 .field("name", name)
                 .field("handler", &"Box<dyn CommandHandler>")
                 .finish(),
+            AppAction::RegisterAsyncCommand(name, _) => f
+                .debug_struct("RegisterAsyncCommand")
+                .field("name", name)
+                .field("handler", &"Box<dyn AsyncCommandHandler>")
+                .finish(),
             AppAction::Info(msg) => f.debug_tuple("Info").field(msg).finish(),
             AppAction::Debug(msg) => f.debug_tuple("Debug").field(msg).finish(),
             AppAction::Warn(msg) => f.debug_tuple("Warn").field(msg).finish(),
             AppAction::Error(msg) => f.debug_tuple("Error").field(msg).finish(),
             AppAction::Critical(msg) => f.debug_tuple("Critical").field(msg).finish(),
+            AppAction::Emit(event) => f.debug_tuple("Emit").field(event).finish(),
             AppAction::Logger(level, msg, _, _) => f
                 .debug_struct("Logger")
                 .field("level", level)
@@ -91,6 +120,110 @@ impl std::fmt::Debug for AppAction {
     }
 }
 
+/// Callback that sees submitted lines first, see [`TerminalApp::set_line_evaluator`].
+pub type LineEvaluator =
+    Box<dyn Fn(&mut TerminalApp, &str) -> Option<String> + Send + Sync + 'static>;
+
+/// Size assumed when the terminal size cannot be determined, e.g. without a TTY.
+pub const DEFAULT_TERMINAL_SIZE: (u16, u16) = (80, 24);
+
+/// Last terminal size seen by any app, packed as `columns << 16 | rows`; zero
+/// until the first query.
+static LAST_TERMINAL_SIZE: AtomicU32 = AtomicU32::new(0);
+
+/// Queries the terminal size and remembers it for detached apps.
+fn query_terminal_size() -> (u16, u16) {
+    let size = terminal::size().unwrap_or(DEFAULT_TERMINAL_SIZE);
+    remember_terminal_size(size);
+    size
+}
+
+fn remember_terminal_size((columns, rows): (u16, u16)) {
+    LAST_TERMINAL_SIZE.store(
+        u32::from(columns) << 16 | u32::from(rows),
+        Ordering::Relaxed,
+    );
+}
+
+/// Returns the last known terminal size without querying the terminal.
+fn last_terminal_size() -> (u16, u16) {
+    match LAST_TERMINAL_SIZE.load(Ordering::Relaxed) {
+        0 => DEFAULT_TERMINAL_SIZE,
+        packed => ((packed >> 16) as u16, packed as u16),
+    }
+}
+
+/// Number of printed lines kept for [`TerminalApp::recent_logs`] by default.
+pub const DEFAULT_RECENT_LOG_CAPACITY: usize = 500;
+
+/// Checks the input line as it is edited, see [`TerminalApp::set_input_validator`].
+pub type InputValidator = Box<dyn Fn(&str) -> Result<(), String> + Send + Sync + 'static>;
+
+/// Picks a color for a command output line, see [`TerminalApp::set_output_highlighter`].
+pub type OutputHighlighter =
+    Box<dyn Fn(&str) -> Option<crossterm::style::Color> + Send + Sync + 'static>;
+
+/// Decides whether a requested exit may proceed, see [`TerminalApp::set_exit_guard`].
+pub type ExitGuard = Box<dyn Fn(&TerminalApp) -> bool + Send + Sync + 'static>;
+
+/// Callback for keys without a built-in binding, see [`TerminalApp::set_unhandled_key_handler`].
+pub type UnhandledKeyHandler = Box<dyn Fn(&mut TerminalApp, KeyEvent) + Send + Sync + 'static>;
+
+/// Callback that sees every raw key event, see [`TerminalApp::set_key_observer`].
+pub type KeyObserver = Box<dyn Fn(&KeyEvent) + Send + Sync + 'static>;
+
+/// What pressing Enter on an empty input line does, see [`TerminalApp::set_on_empty_enter`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EmptyEnterAction {
+    /// Only redraw the prompt (default).
+    #[default]
+    Nothing,
+    /// Run the most recent history entry again.
+    RepeatLast,
+    /// Run the given command line.
+    Run(String),
+}
+
+/// What Ctrl+D does, see [`TerminalApp::set_ctrl_d_behavior`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CtrlDBehavior {
+    /// Always exit (default).
+    #[default]
+    Exit,
+    /// Exit only when the input line is empty, otherwise do nothing.
+    ExitIfEmpty,
+    /// Delete the character under the cursor, or exit when the input line is
+    /// empty, like Ctrl+D in a shell.
+    DeleteChar,
+}
+
+/// How carriage returns in command output are rendered, see
+/// [`TerminalApp::set_carriage_return_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CarriageReturnMode {
+    /// Remove `\r` characters, keeping all text (default).
+    #[default]
+    Strip,
+    /// Let text after a `\r` overwrite the start of the line, like a terminal
+    /// does for progress updates.
+    Overwrite,
+}
+
+/// How control characters in command output are rendered, see
+/// [`TerminalApp::set_control_char_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ControlCharMode {
+    /// Print output unchanged, trusting every escape sequence in it.
+    PassThrough,
+    /// Keep color and style sequences and hyperlinks, and show every other control
+    /// character in caret notation, e.g. `^[` for an escape (default).
+    #[default]
+    Sanitize,
+    /// Like [`Sanitize`](Self::Sanitize), and also replace tabs with spaces up to
+    /// the next multiple of eight columns.
+    ExpandTabs,
+}
+
 /// Main terminal application structure managing state and command execution.
 ///
 /// `TerminalApp` provides a complete terminal interface with:
@@ -99,25 +232,72 @@ impl std::fmt::Debug for AppAction {
 /// - Custom command registration (sync and async)
 /// - Configurable unknown command handling
 /// - Non-blocking async command execution
+///
+/// Everything that does not touch the terminal lives in [`ConsoleCore`], which
+/// `TerminalApp` dereferences to, so registry, history and event methods are
+/// available on the app directly.
 pub struct TerminalApp {
-    pub stdout_handle: Stdout,
-    pub command_history: Vec<String>,
+    stdout_handle: BufWriter<ConsoleOutput>,
     pub current_input: String,
-    pub history_index: Option<usize>,
     pub last_ctrl_c: Option<Instant>,
     pub cursor_position: usize,
-    pub should_exit: bool,
-    pub(crate) commands: HashMap<String, CommandHandlerType>,
-    pub(crate) unknown_command_handler: Option<UnknownCommandHandler>,
-    pub(crate) async_unknown_command_handler: Option<AsyncUnknownCommandHandler>,
-    command_result_rx: Option<mpsc::UnboundedReceiver<CommandResult>>,
-    command_result_tx: Option<mpsc::UnboundedSender<CommandResult>>,
-    running_commands: Vec<RunningCommand>,
+    core: ConsoleCore,
     last_key_event: Option<KeyEvent>,
-    dispatch_event: bool,
-    pub action_sender: Option<mpsc::UnboundedSender<AppAction>>,
-    pub action_receiver: Option<mpsc::UnboundedReceiver<AppAction>>,
-    pub events_tx: Option<broadcast::Sender<events::DaemonConsoleEvent>>,
+    coalesce_flush: bool,
+    cursor_style: SetCursorStyle,
+    cursor_always_visible: bool,
+    suppress_prompt: bool,
+    log_file: Option<BufWriter<File>>,
+    #[cfg(feature = "serde")]
+    json_log_file: Option<BufWriter<File>>,
+    modal: Option<prompt::ModalPrompt>,
+    prompt_outcome: Option<prompt::PromptOutcome>,
+    pager: bool,
+    running: Arc<AtomicBool>,
+    log_groups: Vec<String>,
+    output_highlighter: Option<OutputHighlighter>,
+    /// The input line as it was when the current log batch started.
+    log_batch: Option<(String, usize)>,
+    carriage_return_mode: CarriageReturnMode,
+    control_char_mode: ControlCharMode,
+    exit_guard: Option<ExitGuard>,
+    ctrl_d_behavior: CtrlDBehavior,
+    contexts: Vec<String>,
+    keybindings: Vec<(keys::KeyBinding, keys::KeyAction)>,
+    page_height: Option<usize>,
+    idle_timeout: Option<Duration>,
+    heartbeat_interval: Option<Duration>,
+    last_heartbeat: Instant,
+    last_activity: Instant,
+    ctrl_c_cancels_commands: bool,
+    prompt: String,
+    multiline_prompt: bool,
+    prompt_rows_drawn: u16,
+    status: Option<String>,
+    auto_flush: bool,
+    key_observer: Option<KeyObserver>,
+    unhandled_key_handler: Option<UnhandledKeyHandler>,
+    input_validator: Option<InputValidator>,
+    /// The input last passed to the validator and the error it returned.
+    validated_input: Option<(String, Option<String>)>,
+    block_invalid_submit: bool,
+    line_evaluator: Option<LineEvaluator>,
+    echo_commands_as_log: Option<LogLevel>,
+    echo_input: bool,
+    log_file_path: Option<PathBuf>,
+    startup_diagnostics: bool,
+    on_empty_enter: EmptyEnterAction,
+    prompt_status_color: bool,
+    recent_logs: VecDeque<String>,
+    recent_log_capacity: usize,
+    max_input_len: Option<usize>,
+    input_limit_warned: bool,
+    capture: Option<String>,
+    prompt_job_count: bool,
+    prompt_jobs_shown: usize,
+    input_template: bool,
+    word_boundaries: Vec<char>,
+    terminal_size: (u16, u16),
 }
 
 impl Default for TerminalApp {
@@ -126,6 +306,20 @@ impl Default for TerminalApp {
     }
 }
 
+impl Deref for TerminalApp {
+    type Target = ConsoleCore;
+
+    fn deref(&self) -> &ConsoleCore {
+        &self.core
+    }
+}
+
+impl DerefMut for TerminalApp {
+    fn deref_mut(&mut self) -> &mut ConsoleCore {
+        &mut self.core
+    }
+}
+
 impl TerminalApp {
     /// Removes a character at a specific index in a string.
     fn remove_char_at(&mut self, index: usize) {
@@ -138,113 +332,1152 @@ impl TerminalApp {
 
     /// Creates a new terminal application instance with default settings.
     pub fn new() -> Self {
-        let (tx, rx) = mpsc::unbounded_channel();
-        let (events_tx, _events_rx) = broadcast::channel::<events::DaemonConsoleEvent>(256);
-        let (action_tx, action_rx) = mpsc::unbounded_channel();
+        Self::with_core(ConsoleCore::new())
+    }
+
+    /// Creates a lightweight application that only sends actions through `handle`.
+    ///
+    /// Built on [`ConsoleCore::detached`], so it has no events, action loop or
+    /// command result channel. It writes to standard output unbuffered and takes
+    /// the last terminal size seen by another app instead of querying the
+    /// terminal. Used for the temporary app of the default
+    /// [`execute_with_context`](AsyncCommandHandler::execute_with_context).
+    pub fn detached(handle: Option<ConsoleHandle>) -> Self {
+        Self::from_parts(
+            ConsoleCore::detached(handle),
+            BufWriter::with_capacity(0, ConsoleOutput::stdout()),
+            last_terminal_size(),
+        )
+    }
+
+    /// Creates an application around `core` that renders nothing.
+    ///
+    /// Commands, logging and events work as usual, but all terminal output is
+    /// discarded, as with [`ConsoleOutput::Sink`]. This is the app
+    /// [`ConsoleCore::execute_command`] hands to command handlers.
+    pub fn headless(core: ConsoleCore) -> Self {
+        Self::from_parts(
+            core,
+            BufWriter::with_capacity(0, ConsoleOutput::Sink),
+            last_terminal_size(),
+        )
+    }
+
+    /// Creates a terminal application rendering an existing console core.
+    pub fn with_core(core: ConsoleCore) -> Self {
+        Self::from_parts(
+            core,
+            BufWriter::new(ConsoleOutput::stdout()),
+            query_terminal_size(),
+        )
+    }
+
+    fn from_parts(
+        core: ConsoleCore,
+        stdout_handle: BufWriter<ConsoleOutput>,
+        terminal_size: (u16, u16),
+    ) -> Self {
         Self {
-            stdout_handle: stdout(),
-            command_history: Vec::new(),
+            stdout_handle,
             current_input: String::new(),
-            history_index: None,
             last_ctrl_c: None,
             cursor_position: 0,
-            should_exit: false,
-            commands: HashMap::new(),
-            unknown_command_handler: None,
-            async_unknown_command_handler: None,
-            command_result_rx: Some(rx),
-            command_result_tx: Some(tx),
-            running_commands: Vec::new(),
+            core,
             last_key_event: None,
-            action_sender: Some(action_tx),
-            action_receiver: Some(action_rx),
-            events_tx: Some(events_tx),
-            dispatch_event: true,
+            coalesce_flush: false,
+            cursor_style: SetCursorStyle::DefaultUserShape,
+            cursor_always_visible: false,
+            suppress_prompt: false,
+            log_file: None,
+            #[cfg(feature = "serde")]
+            json_log_file: None,
+            modal: None,
+            prompt_outcome: None,
+            pager: false,
+            running: Arc::new(AtomicBool::new(false)),
+            log_groups: Vec::new(),
+            output_highlighter: None,
+            log_batch: None,
+            carriage_return_mode: CarriageReturnMode::default(),
+            control_char_mode: ControlCharMode::default(),
+            exit_guard: None,
+            ctrl_d_behavior: CtrlDBehavior::default(),
+            contexts: Vec::new(),
+            keybindings: keys::default_keybindings(),
+            page_height: None,
+            idle_timeout: None,
+            heartbeat_interval: None,
+            last_heartbeat: Instant::now(),
+            last_activity: Instant::now(),
+            ctrl_c_cancels_commands: false,
+            prompt: "> ".to_string(),
+            multiline_prompt: false,
+            prompt_rows_drawn: 0,
+            status: None,
+            auto_flush: true,
+            key_observer: None,
+            unhandled_key_handler: None,
+            input_validator: None,
+            validated_input: None,
+            block_invalid_submit: true,
+            line_evaluator: None,
+            echo_commands_as_log: None,
+            echo_input: true,
+            log_file_path: None,
+            startup_diagnostics: false,
+            on_empty_enter: EmptyEnterAction::Nothing,
+            prompt_status_color: false,
+            recent_logs: VecDeque::new(),
+            recent_log_capacity: DEFAULT_RECENT_LOG_CAPACITY,
+            max_input_len: None,
+            input_limit_warned: false,
+            capture: None,
+            prompt_job_count: false,
+            prompt_jobs_shown: 0,
+            input_template: false,
+            word_boundaries: Vec::new(),
+            terminal_size,
         }
     }
 
-    /// Gets a clone of the action sender for communication with async commands
-    pub fn get_action_sender(&self) -> Option<mpsc::UnboundedSender<AppAction>> {
-        self.action_sender.clone()
+    /// Returns the terminal size as `(columns, rows)`.
+    ///
+    /// The size is read once at construction and updated on resize events, so it
+    /// is cheap to call from command handlers. Falls back to
+    /// [`DEFAULT_TERMINAL_SIZE`] when no terminal is attached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::Event;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.process_event(Event::Resize(132, 43)).await.unwrap();
+    ///     assert_eq!(app.terminal_size(), (132, 43));
+    /// }
+    /// ```
+    pub fn terminal_size(&self) -> (u16, u16) {
+        self.terminal_size
     }
 
-    /// Toggles the event dispatch flag
-    fn switch_if_dispatch_event(&mut self) {
-        self.dispatch_event = !self.dispatch_event;
+    /// Returns the headless console core.
+    pub fn core(&self) -> &ConsoleCore {
+        &self.core
     }
 
-    /// Subscribes to daemon console events
-    pub fn subscribe_events(&self) -> Option<broadcast::Receiver<events::DaemonConsoleEvent>> {
-        self.events_tx.as_ref().map(|tx| tx.subscribe())
+    /// Returns the headless console core mutably.
+    pub fn core_mut(&mut self) -> &mut ConsoleCore {
+        &mut self.core
     }
 
-    /// Emits an event to the event channel
-    fn emit_events(&self, _event: events::DaemonConsoleEvent) {
-        if let Some(tx) = &self.events_tx {
-            let _ = tx.send(_event);
-        }
+    /// Consumes the application and returns its console core.
+    pub fn into_core(self) -> ConsoleCore {
+        self.core
     }
 
-    /// Registers a synchronous command with the terminal application
-    pub fn register_command<S: Into<String>>(&mut self, name: S, handler: Box<dyn CommandHandler>) {
-        self.commands
-            .insert(name.into(), CommandHandlerType::PubSync(handler));
+    /// Executes a command line against this app, see [`command::execute_command`].
+    ///
+    /// Shadows [`ConsoleCore::execute_command`], so handlers get this app
+    /// instead of a headless one.
+    pub async fn execute_command(&mut self, command: &str) -> String {
+        command::execute_command(self, command).await
     }
 
-    /// Registers an asynchronous command with the terminal application
-    pub fn register_async_command<S: Into<String>>(
-        &mut self,
-        name: S,
-        handler: Box<dyn AsyncCommandHandler>,
-    ) {
-        self.commands
-            .insert(name.into(), CommandHandlerType::PubAsync(handler));
+    /// Executes a command line and awaits async commands, see
+    /// [`command::execute_command_to_completion`].
+    pub async fn execute_command_to_completion(&mut self, command: &str) -> String {
+        command::execute_command_to_completion(self, command).await
+    }
+
+    /// Replaces the output target, flushing anything pending to the old one first.
+    ///
+    /// Use `ConsoleOutput::Sink` to run commands, logging and events without any
+    /// rendering, e.g. when benchmarking handlers or driving the app headless.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - New output target
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// app.info("Not rendered anywhere");
+    /// ```
+    pub fn set_output(&mut self, output: ConsoleOutput) {
+        let _ = self.stdout_handle.flush();
+        *self.stdout_handle.get_mut() = output;
     }
 
-    /// Sets the action sender for communication with async commands
+    /// Appends all log output to a plain-text file in addition to the terminal.
+    ///
+    /// Color is handled per sink: the terminal keeps ANSI styling (subject to
+    /// `logger::set_color_enabled`), while the file always receives plain text.
     ///
     /// # Arguments
     ///
-    /// * `sender` - The sender to use for sending actions from async commands
-    pub(crate) fn set_action_sender(&mut self, sender: mpsc::UnboundedSender<AppAction>) {
-        self.action_sender = Some(sender);
+    /// * `path` - File to append to, created if missing
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("daemon_console_log_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let path = dir.join("console.log");
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// app.set_log_file(&path).unwrap();
+    /// app.info("Written without colors");
+    /// app.close_log_file();
+    ///
+    /// let text = std::fs::read_to_string(&path).unwrap();
+    /// assert!(text.contains("[Stream/INFO] Written without colors"));
+    /// assert!(!text.contains('\x1b'));
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn set_log_file<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        self.close_log_file();
+        self.log_file = Some(BufWriter::new(file));
+        self.log_file_path = Some(path.as_ref().to_path_buf());
+        Ok(())
     }
 
-    /// Sets a custom handler for unknown commands (synchronous).
+    /// Flushes and closes the log file, if one is open.
+    pub fn close_log_file(&mut self) {
+        if let Some(mut file) = self.log_file.take() {
+            let _ = file.flush();
+        }
+        self.log_file_path = None;
+    }
+
+    /// Appends log entries to `path` as newline-delimited JSON.
+    ///
+    /// Each entry is written as one JSON object per line, e.g.
+    /// `{"ts":1700000000000,"level":"info","module":"Stream","message":"..."}`, for
+    /// log processors; `ts` is in milliseconds and `module` is `null` when the
+    /// entry has none. Lines printed preformatted with
+    /// [`print_log_entry`](Self::print_log_entry) have no structured fields and
+    /// are written with a `null` level and their plain text as the message. This
+    /// sink works alongside [`set_log_file`](Self::set_log_file), and the terminal
+    /// output is unchanged. Entries are buffered and written out once per `run`
+    /// loop iteration, on [`flush`](Self::flush) and on
+    /// [`close_json_log_file`](Self::close_json_log_file). Requires the `serde`
+    /// feature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("daemon_console_json_{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let path = dir.join("console.ndjson");
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// app.set_json_log_file(&path).unwrap();
+    /// app.info_with(Some("Stream"), "Started \"worker\"\nwith two lines");
+    /// app.warn_with(None, "Disk almost full");
+    /// app.close_json_log_file();
+    ///
+    /// let text = std::fs::read_to_string(&path).unwrap();
+    /// let entries: Vec<serde_json::Value> = text
+    ///     .lines()
+    ///     .map(|line| serde_json::from_str(line).unwrap())
+    ///     .collect();
+    /// assert_eq!(entries.len(), 2);
+    /// assert_eq!(entries[0]["level"], "info");
+    /// assert_eq!(entries[0]["module"], "Stream");
+    /// assert_eq!(entries[0]["message"], "Started \"worker\"\nwith two lines");
+    /// assert!(entries[1]["module"].is_null());
+    /// assert!(entries[1]["ts"].is_i64());
+    /// std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn set_json_log_file<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_ref())?;
+        self.close_json_log_file();
+        self.json_log_file = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    /// Flushes and closes the JSON log file, if one is open.
+    #[cfg(feature = "serde")]
+    pub fn close_json_log_file(&mut self) {
+        if let Some(mut file) = self.json_log_file.take() {
+            let _ = file.flush();
+        }
+    }
+
+    /// Writes one entry to the JSON log file, if one is open.
+    #[cfg(feature = "serde")]
+    fn write_json_log(&mut self, level: Option<LogLevel>, message: &str, module: Option<&str>) {
+        if let Some(file) = &mut self.json_log_file {
+            let entry = serde_json::json!({
+                "ts": logger::now().timestamp_millis(),
+                "level": level.map(|level| level.as_str().to_lowercase()),
+                "module": module,
+                "message": message,
+            });
+            let _ = writeln!(file, "{}", entry);
+        }
+    }
+
+    /// Flushes pending JSON log entries, if a JSON log file is open.
+    fn flush_json_log(&mut self) {
+        #[cfg(feature = "serde")]
+        if let Some(file) = &mut self.json_log_file {
+            let _ = file.flush();
+        }
+    }
+
+    /// Writes already plain lines to the log file, if one is open.
+    fn write_log_file(&mut self, plain: &str) {
+        if let Some(file) = &mut self.log_file {
+            let _ = writeln!(file, "{}", plain);
+            let _ = file.flush();
+        }
+    }
+
+    /// Returns whether output is currently discarded.
+    fn output_discarded(&self) -> bool {
+        self.stdout_handle.get_ref().is_sink()
+    }
+
+    /// Sets the cursor shape used while editing the input line.
+    ///
+    /// The terminal's default shape is restored on shutdown.
     ///
     /// # Arguments
     ///
-    /// * `handler` - Closure that takes the full command string and returns a response
-    pub fn set_unknown_command_handler<F>(&mut self, handler: F)
+    /// * `style` - Cursor shape, e.g. `SetCursorStyle::SteadyBar`
+    pub fn set_cursor_style(&mut self, style: SetCursorStyle) {
+        self.cursor_style = style;
+    }
+
+    /// Keeps the cursor visible at all times instead of hiding it during redraws.
+    pub fn set_cursor_always_visible(&mut self, always_visible: bool) {
+        self.cursor_always_visible = always_visible;
+    }
+
+    /// Exits the interactive loop after a period without key or mouse input.
+    ///
+    /// Disabled (`None`) by default. Any keystroke or mouse event restarts the
+    /// countdown. When it expires, `run` shuts down gracefully and emits
+    /// [`events::DaemonConsoleEvent::Shutdown`] with
+    /// [`events::ShutdownReason::Idle`].
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Emits [`events::DaemonConsoleEvent::Heartbeat`] from the `run` loop every
+    /// `interval`.
+    ///
+    /// Disabled (`None`) by default. Heartbeats are sent whether or not there is
+    /// input, so a subscriber that stops receiving them can tell the loop has
+    /// stalled, e.g. in a long sync command. The loop wakes up every 50 ms, which
+    /// bounds the timing precision; intervals below that tick at most once per
+    /// wakeup.
+    pub fn set_heartbeat_interval(&mut self, interval: Option<Duration>) {
+        self.heartbeat_interval = interval;
+        self.last_heartbeat = Instant::now();
+    }
+
+    /// Sets the prompt shown before the input. Defaults to `"> "`.
+    ///
+    /// The prompt may contain ANSI styling; only its visible width is used for
+    /// cursor placement.
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.to_string();
+    }
+
+    /// Sets what pressing Enter on an empty input line does.
+    ///
+    /// A command run this way is echoed and executed like a typed one but is not
+    /// added to the history again, so repeating the last command does not fill the
+    /// history with duplicates. Defaults to [`EmptyEnterAction::Nothing`].
+    ///
+    /// While an async command is still running and streaming its output, an empty
+    /// Enter only redraws the prompt, whatever the action, so a repeated command
+    /// does not interleave with the output of the one in progress. Sync commands
+    /// block key handling until they return, so they are never interrupted this
+    /// way. Background tasks started with
+    /// [`spawn_tracked`](ConsoleCore::spawn_tracked) do not count as running
+    /// commands.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, EmptyEnterAction, TerminalApp};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_on_empty_enter(EmptyEnterAction::RepeatLast);
+    ///     let runs = Arc::new(AtomicUsize::new(0));
+    ///     let counter = runs.clone();
+    ///     app.register_command(
+    ///         "poll",
+    ///         Box::new(move |_: &mut TerminalApp, _: &[&str]| {
+    ///             counter.fetch_add(1, Ordering::SeqCst);
+    ///             String::new()
+    ///         }),
+    ///     );
+    ///
+    ///     app.current_input = "poll".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(runs.load(Ordering::SeqCst), 2);
+    ///     assert_eq!(app.command_history, ["poll"]);
+    /// }
+    /// ```
+    ///
+    /// Empty Enter while an async command streams output:
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use daemon_console::{AsyncCommandHandler, ConsoleOutput, EmptyEnterAction, TerminalApp};
+    /// use std::time::Duration;
+    ///
+    /// #[derive(Clone)]
+    /// struct Tail;
+    ///
+    /// #[async_trait]
+    /// impl AsyncCommandHandler for Tail {
+    ///     async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+    ///         tokio::time::sleep(Duration::from_secs(60)).await;
+    ///         String::new()
+    ///     }
+    ///
+    ///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+    ///         Box::new(self.clone())
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_on_empty_enter(EmptyEnterAction::RepeatLast);
+    ///     app.register_async_command("tail", Box::new(Tail));
+    ///
+    ///     app.current_input = "tail".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(app.running_jobs(), ["tail"]);
+    ///
+    ///     app.abort_running_commands();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(app.running_jobs(), ["tail"]);
+    ///     app.abort_running_commands();
+    /// }
+    /// ```
+    pub fn set_on_empty_enter(&mut self, action: EmptyEnterAction) {
+        self.on_empty_enter = action;
+    }
+
+    /// Replaces the input line, placing the cursor at its end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// app.set_input("restart web");
+    /// assert_eq!(app.current_input, "restart web");
+    /// assert_eq!(app.cursor_position, 11);
+    /// ```
+    pub fn set_input(&mut self, input: &str) {
+        self.input_template = false;
+        self.current_input = input.to_string();
+        self.cursor_position = self.current_input.chars().count();
+        self.history_index = None;
+        let _ = self.render_input_line();
+    }
+
+    /// Pre-fills the input line with a template whose `{}` placeholders are filled in turn.
+    ///
+    /// The cursor starts at the first placeholder. Typing at a placeholder replaces
+    /// it, and Tab jumps to the next remaining placeholder, wrapping around to the
+    /// first. Once none are left, Tab is no longer taken by the template. A template
+    /// without placeholders just sets the input like [`set_input`](Self::set_input).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_input_template("deploy --env {} --version {}");
+    ///
+    ///     let keys = "prod".chars().map(KeyCode::Char)
+    ///         .chain([KeyCode::Tab])
+    ///         .chain("1.2".chars().map(KeyCode::Char));
+    ///     for code in keys {
+    ///         let key = KeyEvent::new(code, KeyModifiers::NONE);
+    ///         app.process_event(Event::Key(key)).await.unwrap();
+    ///     }
+    ///     assert_eq!(app.current_input, "deploy --env prod --version 1.2");
+    /// }
+    /// ```
+    pub fn set_input_template(&mut self, template: &str) {
+        self.set_input(template);
+        self.input_template = self.jump_to_placeholder(0);
+        let _ = self.render_input_line();
+    }
+
+    /// Moves the cursor to the first `{}` at or after char index `from`, wrapping around.
+    ///
+    /// Returns `false`, leaving the cursor alone, when no placeholder is left.
+    fn jump_to_placeholder(&mut self, from: usize) -> bool {
+        let starts: Vec<usize> = self
+            .current_input
+            .match_indices("{}")
+            .map(|(byte_idx, _)| self.current_input[..byte_idx].chars().count())
+            .collect();
+        match starts.iter().find(|&&idx| idx >= from).or(starts.first()) {
+            Some(&idx) => {
+                self.cursor_position = idx;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the template placeholder under the cursor, if there is one.
+    fn take_placeholder_at_cursor(&mut self) {
+        let byte_idx = self
+            .current_input
+            .char_indices()
+            .nth(self.cursor_position)
+            .map_or(self.current_input.len(), |(idx, _)| idx);
+        if self.current_input[byte_idx..].starts_with("{}") {
+            self.current_input.replace_range(byte_idx..byte_idx + 2, "");
+        }
+    }
+
+    /// Sets extra characters that separate words, besides whitespace.
+    ///
+    /// Word motion (Ctrl+Left, Ctrl+Right) and word deletion (Ctrl+W) share these
+    /// boundaries. By default only whitespace separates words; adding `/`, `-` or
+    /// `=` makes paths and flags editable piece by piece.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_word_boundaries(&['/']);
+    ///     app.set_input("cd /var/log/nginx");
+    ///
+    ///     let ctrl_w = KeyEvent::new(KeyCode::Char('w'), KeyModifiers::CONTROL);
+    ///     app.process_event(Event::Key(ctrl_w)).await.unwrap();
+    ///     assert_eq!(app.current_input, "cd /var/log/");
+    ///
+    ///     let ctrl_left = KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL);
+    ///     app.process_event(Event::Key(ctrl_left)).await.unwrap();
+    ///     assert_eq!(app.cursor_position, "cd /var/".len());
+    /// }
+    /// ```
+    pub fn set_word_boundaries(&mut self, boundaries: &[char]) {
+        self.word_boundaries = boundaries.to_vec();
+    }
+
+    /// Returns whether `c` separates words.
+    fn is_word_boundary(&self, c: char) -> bool {
+        c.is_whitespace() || self.word_boundaries.contains(&c)
+    }
+
+    /// Returns the char index where the word before the cursor starts.
+    fn previous_word_start(&self) -> usize {
+        let chars: Vec<char> = self.current_input.chars().collect();
+        let mut pos = self.cursor_position.min(chars.len());
+        while pos > 0 && self.is_word_boundary(chars[pos - 1]) {
+            pos -= 1;
+        }
+        while pos > 0 && !self.is_word_boundary(chars[pos - 1]) {
+            pos -= 1;
+        }
+        pos
+    }
+
+    /// Returns the char index where the word after the cursor ends.
+    fn next_word_end(&self) -> usize {
+        let chars: Vec<char> = self.current_input.chars().collect();
+        let mut pos = self.cursor_position.min(chars.len());
+        while pos < chars.len() && self.is_word_boundary(chars[pos]) {
+            pos += 1;
+        }
+        while pos < chars.len() && !self.is_word_boundary(chars[pos]) {
+            pos += 1;
+        }
+        pos
+    }
+
+    /// Deletes from the start of the previous word up to the cursor.
+    fn delete_previous_word(&mut self) {
+        let start = self.previous_word_start();
+        let end = self.cursor_position.min(self.current_input.chars().count());
+        self.current_input = self
+            .current_input
+            .chars()
+            .enumerate()
+            .filter(|(idx, _)| *idx < start || *idx >= end)
+            .map(|(_, c)| c)
+            .collect();
+        self.cursor_position = start;
+    }
+
+    /// Limits the input line to `max` characters, or removes the limit with `None`.
+    ///
+    /// Protects rendering from accidentally pasted huge strings. The limit counts
+    /// chars, like cursor movement does. Typing past it is ignored, with a single
+    /// warning each time the limit is reached. Unlimited by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_max_input_len(Some(3));
+    ///     for c in "héllo".chars() {
+    ///         let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    ///         app.process_event(Event::Key(key)).await.unwrap();
+    ///     }
+    ///     assert_eq!(app.current_input, "hél");
+    ///     assert_eq!(app.cursor_position, 3);
+    /// }
+    /// ```
+    pub fn set_max_input_len(&mut self, max: Option<usize>) {
+        self.max_input_len = max;
+    }
+
+    /// Shows the number of running async commands and tasks in the prompt.
+    ///
+    /// While jobs run, ` (N)` is inserted after the prompt text, before its
+    /// trailing whitespace, so `"> "` becomes `"> (2) "`. The count disappears
+    /// when nothing runs and is refreshed by the `run` loop as jobs finish.
+    /// Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::Event;
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     app.set_prompt_job_count(true);
+    ///     app.spawn_tracked("sync-cache", std::future::pending::<String>());
+    ///
+    ///     app.process_event(Event::Resize(80, 24)).await.unwrap();
+    ///     let text = captured.take();
+    ///     assert!(text.contains("> (1) "));
+    ///
+    ///     app.abort_running_commands();
+    ///     app.process_event(Event::Resize(80, 24)).await.unwrap();
+    ///     let text = captured.take();
+    ///     assert!(!text.contains("(1)"));
+    /// }
+    /// ```
+    pub fn set_prompt_job_count(&mut self, enabled: bool) {
+        self.prompt_job_count = enabled;
+    }
+
+    /// Colors the prompt red while the last command has failed.
+    ///
+    /// The prompt returns to normal after the next successful command. Nothing is
+    /// colored when colors are disabled with [`logger::set_color_enabled`].
+    /// Off by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,standalone_crate
+    /// use crossterm::style::{Color, SetForegroundColor};
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     app.set_prompt_status_color(true);
+    ///     let red_prompt = format!("{}> ", SetForegroundColor(Color::Red));
+    ///
+    ///     app.current_input = "no-such-command".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     let text = captured.take();
+    ///     assert!(text.contains(&red_prompt));
+    ///
+    ///     app.register_command("ok", Box::new(|_: &mut TerminalApp, _: &[&str]| String::new()));
+    ///     app.current_input = "ok".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     let text = captured.take();
+    ///     assert!(!text.contains(&red_prompt));
+    /// }
+    /// ```
+    pub fn set_prompt_status_color(&mut self, enabled: bool) {
+        self.prompt_status_color = enabled;
+    }
+
+    /// Returns the prompt shown before the input.
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    /// Enters a command context, like a sub-shell scoped to one command.
+    ///
+    /// While contexts are active, every line submitted at the prompt is run with
+    /// the context words in front, so after `push_context("service")` typing
+    /// `restart web` runs `service restart web`. Contexts nest, and the prompt is
+    /// preceded by the active ones. Typing `..` or pressing Ctrl+D leaves the
+    /// innermost context instead of exiting. Lines run with
+    /// [`execute_command`](command::execute_command) are not scoped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_command("service", Box::new(|app: &mut TerminalApp, args: &[&str]| {
+    ///         if args.is_empty() {
+    ///             app.push_context("service");
+    ///         }
+    ///         args.join(" ")
+    ///     }));
+    ///
+    ///     app.set_input("service");
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(app.contexts(), ["service"]);
+    ///
+    ///     app.set_input("restart web");
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(app.last_output(), Some("restart web"));
+    ///
+    ///     app.set_input("..");
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert!(app.contexts().is_empty());
+    ///
+    ///     app.push_context("service");
+    ///     let ctrl_d = Event::Key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+    ///     assert!(!app.process_event(ctrl_d.clone()).await.unwrap());
+    ///     assert!(app.contexts().is_empty());
+    ///     assert!(app.process_event(ctrl_d).await.unwrap());
+    /// }
+    /// ```
+    pub fn push_context(&mut self, context: &str) {
+        self.contexts.push(context.trim().to_string());
+    }
+
+    /// Leaves the innermost command context, returning it.
+    pub fn pop_context(&mut self) -> Option<String> {
+        self.contexts.pop()
+    }
+
+    /// Returns the active command contexts, outermost first.
+    pub fn contexts(&self) -> &[String] {
+        &self.contexts
+    }
+
+    /// Prefixes a submitted command with the active contexts.
+    fn scoped_command<'a>(&self, command: &'a str) -> Cow<'a, str> {
+        if self.contexts.is_empty() {
+            Cow::Borrowed(command)
+        } else {
+            Cow::Owned(format!("{} {}", self.contexts.join(" "), command.trim()))
+        }
+    }
+
+    /// Draws the prompt on its own row with the input on the row below.
+    ///
+    /// Gives the input the full terminal width when the prompt is long. Log lines
+    /// are still inserted above both rows. Defaults to the single-line layout.
+    pub fn set_multiline_prompt(&mut self, multiline: bool) {
+        self.multiline_prompt = multiline;
+    }
+
+    /// Checks the input line after each edit, flagging invalid input as it is typed.
+    ///
+    /// While the validator returns an error, the prompt is tinted red (if colors
+    /// are enabled) and the message is shown dimmed after the input, and Enter
+    /// keeps the line instead of submitting it unless
+    /// [`set_block_invalid_submit`](Self::set_block_invalid_submit) is turned off.
+    /// The validator runs on redraw, at most once per distinct input, so it should
+    /// be a quick syntactic check such as balanced quotes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_input_validator(|line| {
+    ///         if line.matches('"').count() % 2 == 0 {
+    ///             Ok(())
+    ///         } else {
+    ///             Err("unbalanced quotes".to_string())
+    ///         }
+    ///     });
+    ///
+    ///     app.set_input("say \"hi");
+    ///     assert_eq!(app.input_error(), Some("unbalanced quotes"));
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(app.current_input, "say \"hi");
+    ///
+    ///     app.set_input("say \"hi\"");
+    ///     assert_eq!(app.input_error(), None);
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert!(app.current_input.is_empty());
+    /// }
+    /// ```
+    pub fn set_input_validator<F>(&mut self, validator: F)
     where
-        F: Fn(&str) -> String + Send + Sync + 'static,
+        F: Fn(&str) -> Result<(), String> + Send + Sync + 'static,
     {
-        self.unknown_command_handler = Some(Box::new(handler));
+        self.input_validator = Some(Box::new(validator));
+        self.validated_input = None;
     }
 
-    /// Sets a custom handler for unknown commands (asynchronous).
+    /// Removes the input validator.
+    pub fn clear_input_validator(&mut self) {
+        self.input_validator = None;
+        self.validated_input = None;
+    }
+
+    /// Sets whether Enter refuses to submit input the validator rejects.
     ///
-    /// # Arguments
+    /// When off, invalid input is only flagged. On by default.
+    pub fn set_block_invalid_submit(&mut self, block: bool) {
+        self.block_invalid_submit = block;
+    }
+
+    /// Returns the validator's error for the input as of the last redraw.
+    pub fn input_error(&self) -> Option<&str> {
+        self.validated_input
+            .as_ref()
+            .and_then(|(_, error)| error.as_deref())
+    }
+
+    /// Runs the validator if the input changed since it last ran.
+    fn refresh_validation(&mut self) {
+        let Some(validator) = &self.input_validator else {
+            return;
+        };
+        if self
+            .validated_input
+            .as_ref()
+            .is_some_and(|(input, _)| *input == self.current_input)
+        {
+            return;
+        }
+        let error = validator(&self.current_input).err();
+        self.validated_input = Some((self.current_input.clone(), error));
+    }
+
+    /// Routes keys the console has no binding for to a callback.
+    ///
+    /// Built-in handling always comes first: the key observer sees every event, an
+    /// active modal prompt consumes all keys, and the built-in bindings (Enter,
+    /// arrows, Backspace, Ctrl+C, Ctrl+D, Ctrl+W, Ctrl+Left, Ctrl+Right, plain or
+    /// shifted characters, and Tab while an input template has placeholders left)
+    /// cannot be overridden. Only what remains, such as Tab, function keys and other
+    /// Ctrl or Alt combinations, reaches the handler. The input line is redrawn
+    /// afterwards, so the handler may edit `current_input` and `cursor_position`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
     ///
-    /// * `handler` - Closure that takes the full command string and returns a future
-    pub fn set_async_unknown_command_handler<F>(&mut self, handler: F)
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_unhandled_key_handler(|app, key| {
+    ///         if key.code == KeyCode::Tab && app.current_input == "he" {
+    ///             app.current_input = "help".to_string();
+    ///             app.cursor_position = 4;
+    ///         }
+    ///     });
+    ///
+    ///     app.current_input = "he".to_string();
+    ///     app.cursor_position = 2;
+    ///     let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+    ///     app.process_event(Event::Key(tab)).await.unwrap();
+    ///     assert_eq!(app.current_input, "help");
+    /// }
+    /// ```
+    pub fn set_unhandled_key_handler<F>(&mut self, handler: F)
     where
-        F: Fn(&str) -> BoxFuture<'static, String> + Send + Sync + 'static,
+        F: Fn(&mut TerminalApp, KeyEvent) + Send + Sync + 'static,
     {
-        self.async_unknown_command_handler = Some(Box::new(handler));
+        self.unhandled_key_handler = Some(Box::new(handler));
+    }
+
+    /// Removes the unhandled key handler, so such keys are ignored again.
+    pub fn clear_unhandled_key_handler(&mut self) {
+        self.unhandled_key_handler = None;
+    }
+
+    /// Installs a debugging callback that sees every key event before it is handled.
+    ///
+    /// The observer receives raw events, including key releases and repeats that
+    /// the console later ignores, which helps diagnose terminal-specific input
+    /// quirks. It cannot consume or alter events; normal handling always follows.
+    ///
+    /// Keys typed into a hidden-input prompt
+    /// ([`begin_hidden_input`](Self::begin_hidden_input),
+    /// [`read_hidden`](Self::read_hidden)) are not shown to the observer, so
+    /// passwords never reach debug output.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let seen = Arc::new(Mutex::new(Vec::new()));
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     let observed = seen.clone();
+    ///     app.set_key_observer(move |key| observed.lock().unwrap().push(key.code));
+    ///
+    ///     let key = |c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+    ///     app.process_event(key('a')).await.unwrap();
+    ///     app.begin_hidden_input("Password: ");
+    ///     app.process_event(key('s')).await.unwrap();
+    ///
+    ///     assert_eq!(*seen.lock().unwrap(), [KeyCode::Char('a')]);
+    /// }
+    /// ```
+    pub fn set_key_observer<F>(&mut self, observer: F)
+    where
+        F: Fn(&KeyEvent) + Send + Sync + 'static,
+    {
+        self.key_observer = Some(Box::new(observer));
+    }
+
+    /// Removes the key observer.
+    pub fn clear_key_observer(&mut self) {
+        self.key_observer = None;
+    }
+
+    /// Installs an evaluator that sees every submitted line before command dispatch.
+    ///
+    /// Returning `Some(output)` prints the output and skips command dispatch
+    /// entirely, including chaining, redirection and substitution. Returning `None`
+    /// hands the line to the registered commands as usual. History, input and
+    /// command events fire either way, so the console can host a custom REPL while
+    /// keeping its commands available.
+    ///
+    /// # Examples
+    ///
+    /// A calculator for `a + b` lines:
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_handler("list", |_app: &mut TerminalApp, _args: &[&str]| "a b".to_string());
+    ///     app.set_line_evaluator(|_app, line| {
+    ///         let (a, b) = line.split_once('+')?;
+    ///         let sum = a.trim().parse::<i64>().ok()? + b.trim().parse::<i64>().ok()?;
+    ///         Some(sum.to_string())
+    ///     });
+    ///
+    ///     app.current_input = "1 + 2".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(app.last_output(), Some("3"));
+    ///
+    ///     app.current_input = "list".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     assert_eq!(app.last_output(), Some("a b"));
+    ///     assert_eq!(app.command_history, ["1 + 2", "list"]);
+    /// }
+    /// ```
+    pub fn set_line_evaluator<F>(&mut self, evaluator: F)
+    where
+        F: Fn(&mut TerminalApp, &str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.line_evaluator = Some(Box::new(evaluator));
+    }
+
+    /// Removes the line evaluator.
+    pub fn clear_line_evaluator(&mut self) {
+        self.line_evaluator = None;
+    }
+
+    /// Turns the `> line` echo of submitted input on or off, like `set +v` in a shell.
+    ///
+    /// With echo off, submitted lines are still run and their output printed,
+    /// which keeps scripted input from cluttering the console. On by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     app.register_command("ping", Box::new(|_: &mut TerminalApp, _: &[&str]| "pong".to_string()));
+    ///     app.set_echo(false);
+    ///
+    ///     app.current_input = "ping".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///
+    ///     let text = captured.contents();
+    ///     assert!(text.contains("pong\n"));
+    ///     assert!(!text.contains("ping\n"));
+    /// }
+    /// ```
+    pub fn set_echo(&mut self, enabled: bool) {
+        self.echo_input = enabled;
+    }
+
+    /// Also records each submitted command as a log entry at `level`.
+    ///
+    /// The entry reads `command: <line>` and goes through the normal logging path,
+    /// so it reaches the log file and dispatches a `TerminalLog` event, which makes
+    /// it suitable for audit trails. This is independent of the `> line` echo.
+    /// Disabled (`None`) by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp, events::DaemonConsoleEvent, logger::LogLevel};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_echo_commands_as_log(Some(LogLevel::Info));
+    ///     let mut events = app.subscribe_events().unwrap();
+    ///
+    ///     app.current_input = "list".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///
+    ///     let logged = std::iter::from_fn(|| events.try_recv().ok()).any(|event| {
+    ///         matches!(event, DaemonConsoleEvent::TerminalLog { message, .. } if message == "command: list")
+    ///     });
+    ///     assert!(logged);
+    /// }
+    /// ```
+    pub fn set_echo_commands_as_log(&mut self, level: Option<LogLevel>) {
+        self.echo_commands_as_log = level;
+    }
+
+    /// Makes Ctrl+C cancel running async commands before anything else.
+    ///
+    /// When enabled and commands are running, Ctrl+C cancels them like a shell
+    /// interrupting its foreground job, without clearing input or starting the exit
+    /// countdown (see [`ConsoleCore::cancel_running_commands`]). Tasks started
+    /// with `spawn_tracked` are not affected. Disabled by default.
+    pub fn set_ctrl_c_cancels_commands(&mut self, enabled: bool) {
+        self.ctrl_c_cancels_commands = enabled;
+    }
+
+    /// Returns whether the idle timeout has elapsed since the last input.
+    fn idle_expired(&self) -> bool {
+        self.idle_timeout
+            .is_some_and(|timeout| self.last_activity.elapsed() >= timeout)
+    }
+
+    /// Emits a heartbeat event if the heartbeat interval has elapsed.
+    fn emit_heartbeat_if_due(&mut self) {
+        let Some(interval) = self.heartbeat_interval else {
+            return;
+        };
+        if self.last_heartbeat.elapsed() >= interval {
+            self.last_heartbeat = Instant::now();
+            if !self.events_wanted() {
+                return;
+            }
+            self.emit_events(events::DaemonConsoleEvent::Heartbeat {
+                timestamp: events::DaemonConsoleEvent::now_ts(),
+            });
+        }
     }
 
-    /// Removes the custom unknown command handler.
-    pub fn clear_unknown_command_handler(&mut self) {
-        self.unknown_command_handler = None;
-        self.async_unknown_command_handler = None;
+    /// Enables mouse capture and hides the cursor unless it is pinned visible.
+    fn enter_console_mode(&mut self) -> std::io::Result<()> {
+        execute!(self.stdout_handle, EnableMouseCapture)?;
+        if !self.cursor_always_visible {
+            execute!(self.stdout_handle, cursor::Hide)?;
+        }
+        Ok(())
     }
 
     /// Initializes the terminal with raw mode and displays startup messages.
     ///
     /// # Arguments
     ///
-    /// * `startup_message` - Message to display on startup
+    /// * `startup_message` - Message to display on startup, printed like a log
+    ///   entry and skipped when empty
     ///
     /// # Errors
     ///
@@ -256,27 +1489,109 @@ impl TerminalApp {
         self.setup_terminal()?;
 
         if !startup_message.is_empty() {
-            self.print_startup_message(startup_message).await?;
+            self.print_log_entry(startup_message);
+        }
+        if self.startup_diagnostics {
+            self.log_startup_diagnostics();
         }
 
         Ok(())
     }
 
+    /// Logs the effective configuration at startup when enabled.
+    ///
+    /// Off by default. When on, `init_terminal` and `run` call
+    /// [`log_startup_diagnostics`](Self::log_startup_diagnostics) once the terminal
+    /// is set up.
+    pub fn set_startup_diagnostics(&mut self, enabled: bool) {
+        self.startup_diagnostics = enabled;
+    }
+
+    /// Logs the effective console configuration as one multi-line debug entry.
+    ///
+    /// Covers the prompt, log level, color mode, log and history files, mouse
+    /// capture, idle timeout and action channel, so operators can confirm the
+    /// settings in use. Nothing is printed when debug logs are filtered out.
+    pub fn log_startup_diagnostics(&mut self) {
+        let mut lines = vec!["Effective console configuration:".to_string()];
+        lines.extend(self.config_lines().iter().map(|line| format!("  {}", line)));
+        self.logger(
+            LogLevel::Debug,
+            &lines.join("\n"),
+            Some("Diagnostics"),
+            None,
+        );
+    }
+
+    /// Describes the effective configuration, one `name: value` line per setting.
+    pub(crate) fn config_lines(&self) -> Vec<String> {
+        let log_file = self
+            .log_file_path
+            .as_ref()
+            .map_or_else(|| "none".to_string(), |path| path.display().to_string());
+        let idle_timeout = self.idle_timeout.map_or_else(
+            || "disabled".to_string(),
+            |timeout| format!("{:?}", timeout),
+        );
+        let heartbeat = self.heartbeat_interval.map_or_else(
+            || "disabled".to_string(),
+            |interval| format!("every {:?}", interval),
+        );
+        let action_channel = if self.action_sender.is_bounded() {
+            "bounded"
+        } else {
+            "unbounded"
+        };
+        vec![
+            format!(
+                "prompt: {:?} ({})",
+                utils::strip_ansi(&self.prompt),
+                if self.multiline_prompt {
+                    "own line"
+                } else {
+                    "inline"
+                }
+            ),
+            format!("min log level: {}", logger::min_level()),
+            format!(
+                "quiet: {}",
+                if self.quiet {
+                    format!("enabled (showing {} and above)", self.quiet_level)
+                } else {
+                    "disabled".to_string()
+                }
+            ),
+            format!(
+                "color: {}",
+                if logger::color_enabled() {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            ),
+            format!(
+                "timestamps: {}",
+                if logger::show_timestamp() {
+                    "shown"
+                } else {
+                    "hidden"
+                }
+            ),
+            format!("theme: {}", logger::theme().name),
+            format!("log file: {}", log_file),
+            "history file: none (history is kept in memory)".to_string(),
+            format!("history entries: {}", self.command_history.len()),
+            "mouse capture: enabled".to_string(),
+            format!("idle timeout: {}", idle_timeout),
+            format!("heartbeat: {}", heartbeat),
+            format!("action channel: {}", action_channel),
+        ]
+    }
+
     /// Sets up the terminal in raw mode and enables mouse capture
     fn setup_terminal(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         enable_raw_mode()?;
-        execute!(&mut self.stdout_handle, EnableMouseCapture, cursor::Hide)?;
-        self.stdout_handle.flush()?;
-        Ok(())
-    }
-
-    /// Prints the startup message to the terminal
-    async fn print_startup_message(
-        &mut self,
-        message: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        writeln!(self.stdout_handle, "{}", message)?;
-        self.stdout_handle.flush()?;
+        self.enter_console_mode()?;
         Ok(())
     }
 
@@ -298,22 +1613,21 @@ impl TerminalApp {
         &mut self,
         event: Event,
     ) -> Result<bool, Box<dyn std::error::Error>> {
-        let mut should_quit = false;
+        if matches!(event, Event::Key(_) | Event::Mouse(_)) {
+            self.last_activity = Instant::now();
+        }
 
         if let Event::Key(key_event) = &event {
-            // Debugging codes, so fuck you Windows
-            // fyi: https://github.com/crossterm-rs/crossterm/pull/745
-            //
-            // if key_event.kind == KeyEventKind::Press {
-            //     println!("[raw_debug]Key press: {}", key_event.code)
-            // }
-            // if key_event.kind == KeyEventKind::Release {
-            //     println!("[raw_debug]Key release: {}", key_event.code);
-            // }
-            //
+            // Windows reports both press and release events, so the observer sees
+            // releases too; fyi: https://github.com/crossterm-rs/crossterm/pull/745
+            if let Some(observer) = &self.key_observer
+                && !matches!(self.modal, Some(prompt::ModalPrompt::Hidden { .. }))
+            {
+                observer(key_event);
+            }
 
             if key_event.kind == KeyEventKind::Release {
-                return Ok(should_quit);
+                return Ok(false);
             }
 
             if let Some(last_event) = &self.last_key_event
@@ -328,7 +1642,7 @@ impl TerminalApp {
                 };
 
                 if !is_control_key {
-                    return Ok(should_quit);
+                    return Ok(false);
                 }
             }
 
@@ -341,70 +1655,115 @@ impl TerminalApp {
                 }
                 _ => {}
             }
+
+            // A modal prompt owns the keyboard, including Ctrl+C
+            if self.modal.is_some() {
+                self.handle_modal_key(*key_event)?;
+                return Ok(false);
+            }
         }
 
-        if let Event::Key(KeyEvent {
-            code, modifiers, ..
-        }) = event
+        if let Event::Key(
+            key_event @ KeyEvent {
+                code, modifiers, ..
+            },
+        ) = event
         {
+            if let Some(action) = self.key_action(code, modifiers) {
+                return self.handle_key_action(action).await;
+            }
             match code {
-                KeyCode::Char('d') if modifiers == KeyModifiers::CONTROL => {
-                    should_quit = self.handle_ctrl_d().await?;
-                }
-                KeyCode::Char('c') if modifiers == KeyModifiers::CONTROL => {
-                    let (quit, message) = self.handle_ctrl_c().await?;
-                    should_quit = quit;
-                    self.print_log_entry(&message);
-                }
-                KeyCode::Up => {
-                    self.handle_up_key();
-                    self.render_input_line()?;
-                }
-                KeyCode::Down => {
-                    self.handle_down_key();
-                    self.render_input_line()?;
-                }
-                KeyCode::Left => {
-                    if self.cursor_position > 0 {
-                        self.cursor_position -= 1;
-                        self.render_input_line()?;
-                    }
-                }
-                KeyCode::Right => {
-                    if self.cursor_position < self.current_input.chars().count() {
-                        self.cursor_position += 1;
-                        self.render_input_line()?;
-                    }
-                }
-                KeyCode::Enter => {
-                    let should_exit = self.handle_enter_key("> ").await?;
-                    if should_exit {
-                        return Ok(true);
-                    }
-                }
-                KeyCode::Char(c) => {
+                // Ctrl+Alt is how AltGr characters arrive on Windows
+                KeyCode::Char(c)
+                    if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                        || modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
                     self.handle_char_input(c);
                     self.render_input_line()?;
                 }
-                KeyCode::Backspace => {
-                    if self.cursor_position > 0 {
-                        self.remove_char_at(self.cursor_position - 1);
-                        self.cursor_position -= 1;
+                KeyCode::Tab if self.input_template => {
+                    let from = self.cursor_position + 1;
+                    self.input_template = self.jump_to_placeholder(from);
+                    if self.input_template {
                         self.render_input_line()?;
+                    } else {
+                        self.handle_unhandled_key(key_event)?;
                     }
                 }
-                _ => {}
+                _ => self.handle_unhandled_key(key_event)?,
             }
+        } else if let Event::Resize(width, height) = event {
+            self.terminal_size = (width, height);
+            remember_terminal_size(self.terminal_size);
+            self.render_input_line()?;
         }
-        Ok(should_quit)
+        Ok(false)
+    }
+
+    /// Performs the editor action bound to a pressed key.
+    ///
+    /// Returns `true` if the application should exit.
+    async fn handle_key_action(
+        &mut self,
+        action: keys::KeyAction,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        use keys::KeyAction;
+        match action {
+            KeyAction::Submit => {
+                let prompt = self.prompt.clone();
+                return self.handle_enter_key(&prompt).await;
+            }
+            KeyAction::Exit => return self.handle_ctrl_d().await,
+            KeyAction::Interrupt => {
+                let (quit, message) = self.handle_ctrl_c().await?;
+                self.print_log_entry(&message);
+                return Ok(quit);
+            }
+            KeyAction::HistoryPrevious => self.handle_up_key(),
+            KeyAction::HistoryNext => self.handle_down_key(),
+            KeyAction::CursorLeft => self.cursor_position = self.cursor_position.saturating_sub(1),
+            KeyAction::CursorRight => {
+                let char_count = self.current_input.chars().count();
+                self.cursor_position = (self.cursor_position + 1).min(char_count);
+            }
+            KeyAction::WordLeft => self.cursor_position = self.previous_word_start(),
+            KeyAction::WordRight => self.cursor_position = self.next_word_end(),
+            KeyAction::DeleteBackward => {
+                if self.cursor_position > 0 {
+                    self.remove_char_at(self.cursor_position - 1);
+                    self.cursor_position -= 1;
+                }
+            }
+            KeyAction::DeleteWord => self.delete_previous_word(),
+        }
+        self.render_input_line()?;
+        Ok(false)
+    }
+
+    /// Passes a key without a built-in binding to the unhandled key handler.
+    fn handle_unhandled_key(&mut self, key: KeyEvent) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(handler) = self.unhandled_key_handler.take() else {
+            return Ok(());
+        };
+        handler(self, key);
+        // Keep a replacement the handler may have installed
+        if self.unhandled_key_handler.is_none() {
+            self.unhandled_key_handler = Some(handler);
+        }
+        let char_count = self.current_input.chars().count();
+        self.cursor_position = self.cursor_position.min(char_count);
+        self.render_input_line()
     }
 
     /// Shuts down the terminal and displays exit messages.
     ///
+    /// The exit message is printed while raw mode is still active, exactly like log
+    /// entries, and only then is the terminal restored. See
+    /// [`run_with_messages`](Self::run_with_messages) for the same ordering.
+    ///
     /// # Arguments
     ///
-    /// * `stdout` - Mutable reference to standard output
-    /// * `exit_message` - Message to display on exit
+    /// * `exit_message` - Message to display on exit, skipped when empty
     ///
     /// # Errors
     ///
@@ -413,9 +1772,31 @@ impl TerminalApp {
         &mut self,
         exit_message: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        self.teardown_terminal((!exit_message.is_empty()).then_some(exit_message))
+    }
+
+    /// Prints the exit message, clears the input line and restores the terminal.
+    ///
+    /// The order matters: printing after `disable_raw_mode` would bypass the input
+    /// line handling and render multi-line or colored messages inconsistently.
+    fn teardown_terminal(
+        &mut self,
+        exit_message: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(message) = exit_message {
+            self.print_log_entry(message);
+        }
+        self.clear_input_line();
+
+        self.abort_running_commands();
+        self.coalesce_flush = false;
         disable_raw_mode()?;
-        writeln!(self.stdout_handle, "{}", exit_message)?;
-        self.stdout_handle.flush()?;
+        execute!(
+            self.stdout_handle,
+            DisableMouseCapture,
+            SetCursorStyle::DefaultUserShape,
+            cursor::Show
+        )?;
         Ok(())
     }
 
@@ -427,48 +1808,121 @@ impl TerminalApp {
     ///
     /// # Arguments
     ///
-    /// * `startup_message` - Optional message to display on startup
-    /// * `exit_message` - Optional message to display on exit
+    /// * `startup_message` - Message to display on startup, skipped when empty
+    /// * `exit_message` - Message to display on exit, skipped when empty
     ///
     /// # Errors
     ///
-    /// Returns an error if terminal initialization or event handling fails.
-    pub async fn run(
+    /// Returns an error if terminal initialization or event handling fails.
+    pub async fn run(
+        &mut self,
+        startup_message: &str,
+        exit_message: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let non_empty = |message: &str| (!message.is_empty()).then(|| message.to_string());
+        self.run_with_messages(non_empty(startup_message), non_empty(exit_message))
+            .await
+    }
+
+    /// Same as [`run`](Self::run), with optional startup and exit messages.
+    ///
+    /// Both messages are printed through `print_log_entry` while the console is still
+    /// active, so multi-line and colored banners render the same way on startup and
+    /// exit. The input line is cleared after the exit message, before teardown.
+    ///
+    /// # Arguments
+    ///
+    /// * `startup_message` - Message to display on startup, or `None`
+    /// * `exit_message` - Message to display on exit, or `None`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if terminal initialization or event handling fails.
+    pub async fn run_with_messages(
+        &mut self,
+        startup_message: Option<String>,
+        exit_message: Option<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.running.store(true, Ordering::SeqCst);
+        let result = self.run_loop(startup_message, exit_message).await;
+        self.running.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// Returns whether [`run`](Self::run) is active.
+    ///
+    /// True from the start of `run` until the terminal has been torn down, so
+    /// embedders can tell whether output goes through the console or should be
+    /// printed plainly. Other tasks can watch the same state through
+    /// [`running_flag`](Self::running_flag). Not to be confused with
+    /// [`is_running`](ConsoleCore::is_running), which checks a single command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     let flag = app.running_flag();
+    ///     assert!(!app.is_console_running());
+    ///
+    ///     // Exits right away, or fails without a terminal; either way it is over
+    ///     app.should_exit = true;
+    ///     let _ = app.run("", "").await;
+    ///     assert!(!app.is_console_running());
+    ///     assert!(!flag.load(Ordering::SeqCst));
+    /// }
+    /// ```
+    pub fn is_console_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Returns a shared flag mirroring [`is_console_running`](Self::is_console_running).
+    pub fn running_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.running)
+    }
+
+    /// The body of [`run_with_messages`](Self::run_with_messages).
+    async fn run_loop(
         &mut self,
-        startup_message: &str,
-        exit_message: &str,
+        startup_message: Option<String>,
+        exit_message: Option<String>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut action_rx = self.action_receiver.take().unwrap();
 
         enable_raw_mode()?;
-        execute!(self.stdout_handle, EnableMouseCapture, cursor::Hide)?;
+        self.enter_console_mode()?;
+        self.coalesce_flush = true;
 
-        if !startup_message.is_empty() {
-            self.print_log_entry(startup_message);
+        if let Some(message) = startup_message {
+            self.print_log_entry(&message);
+        }
+        if self.startup_diagnostics {
+            self.log_startup_diagnostics();
         }
 
-        loop {
-            // Handle AppAction messages
-            while let Ok(action) = action_rx.try_recv() {
-                match action {
-                    AppAction::RegisterCommand(name, handler) => {
-                        self.register_command(name, handler);
-                    }
-                    AppAction::Info(_)
-                    | AppAction::Debug(_)
-                    | AppAction::Warn(_)
-                    | AppAction::Error(_)
-                    | AppAction::Critical(_) => {
-                        self.handle_log_action(action);
-                    }
-                    AppAction::Logger(level, message, module_name, dispatch_event) => {
-                        self.handle_logger_action(level, message, module_name, dispatch_event);
+        self.last_activity = Instant::now();
+        self.last_heartbeat = Instant::now();
+        let reason = loop {
+            // Handle AppAction messages, redrawing the input line once
+            if let Some(first) = action_rx.try_recv() {
+                self.log_batch(|app| {
+                    app.apply_action(first);
+                    while let Some(action) = action_rx.try_recv() {
+                        app.apply_action(action);
                     }
-                }
+                });
             }
 
             // Check for completed async commands
-            self.check_running_commands().await?;
+            self.check_running_commands();
+            if self.prompt_job_count && self.running_jobs().len() != self.prompt_jobs_shown {
+                self.render_input_line()?;
+            }
 
             // Process command results
             if let Some(ref mut rx) = self.command_result_rx
@@ -477,6 +1931,10 @@ impl TerminalApp {
                 self.handle_command_result(result).await?;
             }
 
+            // Write out everything produced this iteration before waiting
+            self.stdout_handle.flush()?;
+            self.flush_json_log();
+
             // Handle terminal events (non-blocking)
             tokio::select! {
                 _ = tokio::time::sleep(tokio::time::Duration::from_millis(50)) => {
@@ -484,33 +1942,108 @@ impl TerminalApp {
                     if poll(std::time::Duration::from_millis(0))?
                         && let Ok(event) = event::read()
                             && self.process_event(event).await? {
-                                break;
+                                break events::ShutdownReason::User;
                             }
                 }
             }
 
-            if self.should_exit {
-                break;
+            if self.should_exit && self.exit_allowed() {
+                break events::ShutdownReason::Requested;
             }
-        }
 
-        disable_raw_mode()?;
-        execute!(self.stdout_handle, DisableMouseCapture, cursor::Show)?;
+            if self.idle_expired() {
+                self.print_log_entry(&get_warn!(
+                    "No input received within the idle timeout, exiting.",
+                    "Daemon Console"
+                ));
+                break events::ShutdownReason::Idle;
+            }
+
+            self.emit_heartbeat_if_due();
+        };
 
-        if !exit_message.is_empty() {
-            println!("{}", exit_message);
+        if self.events_wanted() {
+            self.emit_events(events::DaemonConsoleEvent::Shutdown {
+                reason,
+                timestamp: events::DaemonConsoleEvent::now_ts(),
+            });
         }
+        self.teardown_terminal(exit_message.as_deref())
+    }
 
-        Ok(())
+    /// Executes a single command line and exits without entering the interactive loop.
+    ///
+    /// This is one-shot mode, for using the console as a CLI tool
+    /// (e.g. `daemon_console --exec "help"`). The terminal stays in normal mode and no
+    /// prompt is drawn. Unlike interactive `execute_command`, async commands are
+    /// awaited to completion instead of being started in the background, so the
+    /// printed and returned output is always the command's final result. Printed
+    /// lines get the same carriage return and control character handling as
+    /// interactive output.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - Full command line including arguments
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConsoleError::EmptyCommand` for a blank line, or `ConsoleError::Io`
+    /// if writing the output fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     app.register_command(
+    ///         "progress",
+    ///         Box::new(|_: &mut TerminalApp, _: &[&str]| -> String {
+    ///             "\x1b[2J50%\r100%".to_string()
+    ///         }),
+    ///     );
+    ///
+    ///     app.run_once("progress").await.unwrap();
+    ///     assert_eq!(captured.contents(), "^[[2J50%100%\n");
+    /// }
+    /// ```
+    pub async fn run_once(&mut self, line: &str) -> Result<String, ConsoleError> {
+        if line.trim().is_empty() {
+            return Err(ConsoleError::EmptyCommand);
+        }
+        self.suppress_prompt = true;
+        let output = command::execute_command_to_completion(self, line).await;
+        for output_line in output.lines() {
+            self.print_output_line(output_line);
+        }
+        self.suppress_prompt = false;
+        self.stdout_handle.flush()?;
+        self.last_output = Some(output.clone());
+        Ok(output)
     }
 
     /// Clear the current input line and re-renders it.
     pub fn clear_input_line(&mut self) {
-        let _ = execute!(
+        if self.output_discarded() || self.suppress_prompt {
+            return;
+        }
+        let _ = queue!(
             self.stdout_handle,
             cursor::MoveToColumn(0),
             Clear(ClearType::CurrentLine)
         );
+        for _ in 0..self.prompt_rows_drawn {
+            let _ = queue!(
+                self.stdout_handle,
+                cursor::MoveUp(1),
+                Clear(ClearType::CurrentLine)
+            );
+        }
+        self.prompt_rows_drawn = 0;
     }
 
     /// Prints a log entry while preserving the input line.
@@ -526,34 +2059,518 @@ impl TerminalApp {
     ///
     /// Returns an error if writing to stdout fails.
     pub fn print_log_entry(&mut self, log_line: &str) {
+        self.write_log_file(&utils::strip_ansi(log_line));
+        #[cfg(feature = "serde")]
+        if self.json_log_file.is_some() {
+            self.write_json_log(None, &utils::strip_ansi(log_line), None);
+        }
+        self.print_terminal_entry(log_line);
+    }
+
+    /// Starts a group: `label` is printed, and later entries are indented under it.
+    ///
+    /// Groups nest, each level adding two spaces of indentation on the terminal;
+    /// the log file is not indented. A group opened by a sync command is closed
+    /// automatically when the command returns, even without
+    /// [`end_log_group`](Self::end_log_group).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{TerminalApp, utils};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// let text = app.capture_output(|app| {
+    ///     app.begin_log_group("Deploy");
+    ///     app.info("uploading");
+    ///     app.begin_log_group("Migrate");
+    ///     app.info("step 1");
+    ///     app.end_log_group();
+    ///     app.end_log_group();
+    ///     app.info("done");
+    /// });
+    ///
+    /// let text = utils::strip_ansi(&text);
+    /// let line = |end: &str| text.lines().find(|line| line.ends_with(end)).unwrap().to_string();
+    /// assert!(line("Deploy").starts_with("Deploy"));
+    /// assert!(line("uploading").starts_with("  ["));
+    /// assert!(line("Migrate").starts_with("  Migrate"));
+    /// assert!(line("step 1").starts_with("    ["));
+    /// assert!(line("done").starts_with('['));
+    /// ```
+    pub fn begin_log_group(&mut self, label: &str) {
+        self.print_log_entry(label);
+        self.log_groups.push(label.to_string());
+    }
+
+    /// Closes the innermost group opened with [`begin_log_group`](Self::begin_log_group).
+    ///
+    /// Does nothing when no group is open.
+    pub fn end_log_group(&mut self) {
+        self.log_groups.pop();
+    }
+
+    /// Closes groups until at most `depth` remain open.
+    pub(crate) fn close_log_groups(&mut self, depth: usize) {
+        self.log_groups.truncate(depth);
+    }
+
+    /// Returns how many log groups are open.
+    pub fn log_group_depth(&self) -> usize {
+        self.log_groups.len()
+    }
+
+    /// Shows a transient single-line status in place of the input line.
+    ///
+    /// The text is written without a trailing newline and the input line is not
+    /// redrawn, so calling it repeatedly updates the same row, e.g. for a simple
+    /// progress counter. The next log entry or input redraw overwrites it. The
+    /// status is not written to the log file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// for done in 1..=3 {
+    ///     app.print_inline(&format!("Uploading... {}/3", done));
+    /// }
+    /// app.info("Upload finished");
+    /// ```
+    pub fn print_inline(&mut self, text: &str) {
+        if self.output_discarded() || self.capture.is_some() {
+            return;
+        }
+        self.clear_input_line();
+        let _ = write!(self.stdout_handle, "{}", text);
+        let _ = self.flush_output();
+    }
+
+    /// Shows `status` on a line of its own above the input line.
+    ///
+    /// The status line is meant for transient state such as the progress of a
+    /// long-running command. Log entries scroll above it, and it is redrawn and
+    /// flushed right away (unless [auto-flush](Self::set_auto_flush) is off), so
+    /// a sync command can update it while it runs. Text
+    /// wider than the terminal is truncated. Handlers that set a status should
+    /// clear it with [`clear_status`](Self::clear_status) when they are done.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::command::execute_command;
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     app.register_command("scan", Box::new(|app: &mut TerminalApp, _: &[&str]| {
+    ///         for step in 1..=2 {
+    ///             app.set_status(&format!("Scanning {}/2", step));
+    ///         }
+    ///         app.clear_status();
+    ///         "scan done".to_string()
+    ///     }));
+    ///
+    ///     execute_command(&mut app, "scan").await;
+    ///     let text = captured.contents();
+    ///     assert!(text.contains("Scanning 1/2") && text.contains("Scanning 2/2"));
+    ///     assert_eq!(app.status(), None);
+    /// }
+    /// ```
+    pub fn set_status(&mut self, status: &str) {
+        self.status = Some(status.replace(['\r', '\n'], " "));
+        self.redraw_status();
+    }
+
+    /// Removes the status line.
+    pub fn clear_status(&mut self) {
+        if self.status.take().is_some() {
+            self.redraw_status();
+        }
+    }
+
+    /// Returns the text of the status line, if one is shown.
+    pub fn status(&self) -> Option<&str> {
+        self.status.as_deref()
+    }
+
+    /// Redraws the status and input lines, flushing even inside the `run` loop.
+    fn redraw_status(&mut self) {
+        if self.render_input_line().is_ok() && self.log_batch.is_none() && self.auto_flush {
+            let _ = self.stdout_handle.flush();
+        }
+    }
+
+    /// Sets how many printed lines [`recent_logs`](Self::recent_logs) keeps.
+    ///
+    /// Defaults to [`DEFAULT_RECENT_LOG_CAPACITY`]; `0` disables the buffer.
+    /// Shrinking drops the oldest lines.
+    pub fn set_recent_log_capacity(&mut self, capacity: usize) {
+        self.recent_log_capacity = capacity;
+        while self.recent_logs.len() > capacity {
+            self.recent_logs.pop_front();
+        }
+    }
+
+    /// Returns the most recently printed log entries and command output, oldest first.
+    ///
+    /// Entries are stored as printed, including ANSI styling.
+    pub fn recent_logs(&self) -> impl Iterator<Item = &str> {
+        self.recent_logs.iter().map(String::as_str)
+    }
+
+    /// Clears the screen and reprints the last `n` entries of [`recent_logs`](Self::recent_logs),
+    /// followed by the input line.
+    ///
+    /// This is a display operation only: the entries are not added to the buffer
+    /// again, written to the log file or dispatched as events. Useful after the
+    /// screen was resized or overdrawn by another program.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the terminal fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Writer(Box::new(std::io::sink())));
+    /// for step in ["fetch", "build", "deploy"] {
+    ///     app.info(step);
+    /// }
+    /// app.reprint_recent(2).unwrap();
+    /// assert_eq!(app.recent_logs().count(), 3);
+    /// ```
+    pub fn reprint_recent(&mut self, n: usize) -> Result<(), Box<dyn std::error::Error>> {
+        if self.output_discarded() {
+            return Ok(());
+        }
+        self.prompt_rows_drawn = 0;
+        queue!(
+            self.stdout_handle,
+            Clear(ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+        let skip = self.recent_logs.len().saturating_sub(n);
+        for line in self.recent_logs.iter().skip(skip) {
+            queue!(self.stdout_handle, cursor::MoveToColumn(0))?;
+            writeln!(self.stdout_handle, "{}", line)?;
+        }
+        self.render_input_line()
+    }
+
+    /// Runs `f` with everything it prints collected into a string instead of shown.
+    ///
+    /// Log entries and command output printed while `f` runs are returned as text,
+    /// one line per entry and including ANSI styling, instead of reaching the
+    /// terminal; the input line is not redrawn meanwhile. Entries are still written
+    /// to the log file. Normal output is restored afterwards, also when `f` panics.
+    /// Captures nest: an inner capture takes only what is printed inside it.
+    ///
+    /// Only output produced synchronously inside `f` on this app is captured.
+    /// Async commands started from `f` and logs sent through the action channel
+    /// print after the capture has ended.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp, utils};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// let captured = app.capture_output(|app| {
+    ///     app.info("disk ok");
+    ///     app.warn("disk almost full");
+    /// });
+    /// let warnings: Vec<String> = utils::strip_ansi(&captured)
+    ///     .lines()
+    ///     .filter(|line| line.contains("WARN"))
+    ///     .map(str::to_string)
+    ///     .collect();
+    /// assert_eq!(warnings.len(), 1);
+    /// assert!(warnings[0].ends_with("disk almost full"));
+    /// ```
+    pub fn capture_output<F>(&mut self, f: F) -> String
+    where
+        F: FnOnce(&mut TerminalApp),
+    {
+        let outer = self.capture.replace(String::new());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(self)));
+        let captured = std::mem::replace(&mut self.capture, outer).unwrap_or_default();
+        if let Err(payload) = result {
+            std::panic::resume_unwind(payload);
+        }
+        captured
+    }
+
+    /// Prints the log entries written by `f` with a single input line redraw.
+    ///
+    /// Normally every entry clears and redraws the input line, so heavy logging
+    /// while typing makes the cursor jump between column 0 and the input. Within
+    /// a batch the cursor is hidden, entries are written one after another, and
+    /// the input line is drawn once at the end. If `f` leaves the input text
+    /// unchanged, the cursor is put back exactly where it was. The `run` loop
+    /// applies queued actions in a batch. Batches nest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     for c in "abc".chars() {
+    ///         let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+    ///         app.process_event(Event::Key(key)).await.unwrap();
+    ///     }
+    ///     let left = KeyEvent::new(KeyCode::Left, KeyModifiers::NONE);
+    ///     app.process_event(Event::Key(left)).await.unwrap();
+    ///
+    ///     captured.clear();
+    ///     app.log_batch(|app| {
+    ///         for i in 0..3 {
+    ///             app.info(&format!("tick {}", i));
+    ///         }
+    ///     });
+    ///     let text = captured.contents();
+    ///     // One redraw for the whole batch, after the last entry
+    ///     assert_eq!(text.matches("> abc").count(), 1);
+    ///     assert!(text.find("tick 2").unwrap() < text.find("> abc").unwrap());
+    ///     assert_eq!(app.cursor_position, 2);
+    /// }
+    /// ```
+    pub fn log_batch<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut TerminalApp),
+    {
+        if self.log_batch.is_some() {
+            f(self);
+            return;
+        }
+        if !self.output_discarded() && self.capture.is_none() {
+            let _ = queue!(self.stdout_handle, cursor::Hide);
+        }
+        self.log_batch = Some((self.current_input.clone(), self.cursor_position));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(self)));
+        if let Some((input, cursor)) = self.log_batch.take() {
+            let len = self.current_input.chars().count();
+            self.cursor_position = if input == self.current_input {
+                cursor
+            } else {
+                self.cursor_position.min(len)
+            };
+        }
+        let _ = self.render_input_line();
+        if let Err(payload) = result {
+            std::panic::resume_unwind(payload);
+        }
+    }
+
+    /// Prints a line to the terminal only, preserving the input line.
+    fn print_terminal_entry(&mut self, log_line: &str) {
+        let indented;
+        let log_line = if self.log_groups.is_empty() {
+            log_line
+        } else {
+            let indent = "  ".repeat(self.log_groups.len());
+            indented = log_line
+                .split('\n')
+                .map(|line| format!("{}{}", indent, line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            &indented
+        };
+        if let Some(captured) = &mut self.capture {
+            captured.push_str(log_line);
+            captured.push('\n');
+            return;
+        }
+        if self.recent_log_capacity > 0 {
+            if self.recent_logs.len() == self.recent_log_capacity {
+                self.recent_logs.pop_front();
+            }
+            self.recent_logs.push_back(log_line.to_string());
+        }
         self.clear_input_line();
         let _ = writeln!(self.stdout_handle, "{}", log_line);
-        let _ = self.stdout_handle.flush();
         let _ = self.render_input_line();
     }
 
+    /// Flushes output after every write, or leaves flushing to the host.
+    ///
+    /// On by default. With auto-flush off, log entries and redraws collect in the
+    /// output buffer until [`flush`](Self::flush) is called, so an embedder that
+    /// shares the terminal with other output can repaint everything at once.
+    /// Output still reaches the terminal early when it overflows the buffer, and
+    /// the `run` loop and modal prompts keep flushing before they wait for input,
+    /// so nothing is held back while the console is idle. Turning auto-flush back
+    /// on flushes what is pending.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    /// use std::io::Write;
+    ///
+    /// let captured = OutputBuffer::new();
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    /// app.set_auto_flush(false);
+    ///
+    /// app.info("held back");
+    /// assert!(captured.is_empty());
+    /// app.flush().unwrap();
+    /// let text = captured.contents();
+    /// assert!(text.contains("held back"));
+    /// ```
+    pub fn set_auto_flush(&mut self, enabled: bool) {
+        self.auto_flush = enabled;
+        if enabled {
+            let _ = self.stdout_handle.flush();
+        }
+    }
+
+    /// Writes all buffered output to the terminal and the JSON log file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing to the terminal fails.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.flush_json_log();
+        self.stdout_handle.flush()
+    }
+
+    /// Flushes buffered output unless the `run` loop is coalescing flushes or
+    /// auto-flush is off.
+    ///
+    /// Inside `run`, output is flushed once per loop iteration before waiting for
+    /// input, so bursts of keystrokes and logs end up in a single write.
+    fn flush_output(&mut self) -> std::io::Result<()> {
+        if self.coalesce_flush || !self.auto_flush {
+            Ok(())
+        } else {
+            self.stdout_handle.flush()
+        }
+    }
+
     /// Renders the input line with prompt and cursor positioning.
-    fn render_input_line(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    pub(crate) fn render_input_line(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.refresh_validation();
+        if self.output_discarded() || self.capture.is_some() || self.log_batch.is_some() {
+            return Ok(());
+        }
+        if self.suppress_prompt {
+            self.flush_output()?;
+            return Ok(());
+        }
         let result = (|| -> Result<(), Box<dyn std::error::Error>> {
-            execute!(self.stdout_handle, cursor::Hide)?;
+            if !self.cursor_always_visible {
+                queue!(self.stdout_handle, cursor::Hide)?;
+            }
             self.clear_input_line();
-            execute!(
+            let (mut prompt, input, cursor_chars) = match &self.modal {
+                Some(modal) => (modal.prompt_text(), String::new(), 0),
+                None => (
+                    self.prompt.clone(),
+                    self.current_input.clone(),
+                    self.cursor_position,
+                ),
+            };
+            if self.modal.is_none() && !self.contexts.is_empty() {
+                prompt = format!("{} {}", self.contexts.join(" "), prompt);
+            }
+            if self.modal.is_none() && self.prompt_job_count {
+                let jobs = self.running_jobs().len();
+                self.prompt_jobs_shown = jobs;
+                if jobs > 0 {
+                    let body = prompt.trim_end();
+                    prompt = format!("{} ({}){}", body, jobs, &prompt[body.len()..]);
+                }
+            }
+            let input_error = match self.modal {
+                Some(_) => None,
+                None => self.input_error().map(str::to_string),
+            };
+            if self.modal.is_none()
+                && logger::color_enabled()
+                && (input_error.is_some()
+                    || (self.prompt_status_color && !self.last_command_succeeded()))
+            {
+                prompt = format!(
+                    "{}{}{}",
+                    crossterm::style::SetForegroundColor(crossterm::style::Color::Red),
+                    prompt,
+                    crossterm::style::ResetColor
+                );
+            }
+            if let Some(status) = &self.status {
+                // Truncated so the row never wraps and the rows can be cleared again
+                let width = (self.terminal_size.0 as usize).saturating_sub(1);
+                let status = utils::truncate_to_width(status, width);
+                if logger::color_enabled() {
+                    queue!(
+                        self.stdout_handle,
+                        crossterm::style::SetAttribute(crossterm::style::Attribute::Reverse),
+                        crossterm::style::Print(status),
+                        crossterm::style::SetAttribute(crossterm::style::Attribute::Reset),
+                    )?;
+                } else {
+                    queue!(self.stdout_handle, crossterm::style::Print(status))?;
+                }
+                queue!(self.stdout_handle, crossterm::style::Print("\r\n"))?;
+                self.prompt_rows_drawn += 1;
+            }
+            if self.multiline_prompt && self.modal.is_none() {
+                queue!(
+                    self.stdout_handle,
+                    crossterm::style::Print(&prompt),
+                    crossterm::style::Print("\r\n")
+                )?;
+                self.prompt_rows_drawn += 1;
+                prompt.clear();
+            }
+            queue!(
                 self.stdout_handle,
-                crossterm::style::Print("> "),
-                crossterm::style::Print(&self.current_input)
+                crossterm::style::Print(&prompt),
+                crossterm::style::Print(&input)
             )?;
-            let visual_cursor_pos = 2 + self
-                .current_input
-                .chars()
-                .take(self.cursor_position)
-                .map(|c| c.width().unwrap_or(0))
-                .sum::<usize>();
-            execute!(
+            if let Some(error) = &input_error {
+                let hint = format!("  ({})", error);
+                if logger::color_enabled() {
+                    queue!(
+                        self.stdout_handle,
+                        crossterm::style::SetAttribute(crossterm::style::Attribute::Dim),
+                        crossterm::style::Print(hint),
+                        crossterm::style::SetAttribute(crossterm::style::Attribute::Reset)
+                    )?;
+                } else {
+                    queue!(self.stdout_handle, crossterm::style::Print(hint))?;
+                }
+            }
+            let visual_cursor_pos = utils::display_width(&prompt)
+                + input
+                    .chars()
+                    .take(cursor_chars)
+                    .map(|c| c.width().unwrap_or(0))
+                    .sum::<usize>();
+            queue!(
                 self.stdout_handle,
                 cursor::MoveToColumn(visual_cursor_pos as u16),
+                self.cursor_style,
                 cursor::Show
             )?;
-            self.stdout_handle.flush()?;
+            self.flush_output()?;
             Ok(())
         })();
         if result.is_err() {
@@ -562,23 +2579,154 @@ impl TerminalApp {
         result
     }
 
-    /// Handles Ctrl+D key press, signaling application exit.
+    /// Handles Ctrl+D key press according to the [`CtrlDBehavior`].
+    ///
+    /// Where Ctrl+D would exit, it leaves the innermost
+    /// [command context](Self::push_context) instead while one is active.
     ///
     /// # Returns
     ///
-    /// `Ok(true)` to signal the application should quit.
+    /// `Ok(true)` to signal the application should quit, unless the
+    /// [exit guard](Self::set_exit_guard) refuses.
     pub async fn handle_ctrl_d(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
-        Ok(true)
+        let exits = self.current_input.is_empty()
+            || match self.ctrl_d_behavior {
+                CtrlDBehavior::Exit => true,
+                CtrlDBehavior::ExitIfEmpty => false,
+                CtrlDBehavior::DeleteChar => {
+                    self.remove_char_at(self.cursor_position);
+                    self.render_input_line()?;
+                    false
+                }
+            };
+        if !exits {
+            return Ok(false);
+        }
+        if self.contexts.pop().is_some() {
+            self.render_input_line()?;
+            return Ok(false);
+        }
+        Ok(self.exit_allowed())
+    }
+
+    /// Sets what Ctrl+D does while the input line is not empty.
+    ///
+    /// On an empty line Ctrl+D always exits. Defaults to [`CtrlDBehavior::Exit`],
+    /// which exits regardless of the input.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, CtrlDBehavior, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.set_ctrl_d_behavior(CtrlDBehavior::ExitIfEmpty);
+    ///     let ctrl_d = Event::Key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+    ///
+    ///     app.set_input("status");
+    ///     assert!(!app.process_event(ctrl_d.clone()).await.unwrap());
+    ///     assert_eq!(app.current_input, "status");
+    ///
+    ///     app.set_input("");
+    ///     assert!(app.process_event(ctrl_d).await.unwrap());
+    /// }
+    /// ```
+    pub fn set_ctrl_d_behavior(&mut self, behavior: CtrlDBehavior) {
+        self.ctrl_d_behavior = behavior;
+    }
+
+    /// Asks `guard` before exiting on Ctrl+D or when a command sets `should_exit`.
+    ///
+    /// Meant for consoles with state worth protecting, e.g. unsaved changes. While
+    /// the guard returns `false`, the exit is cancelled with a warning and
+    /// `should_exit` is reset. Forced shutdowns are not guarded: the Ctrl+C
+    /// double press, the idle timeout, and the process being terminated by a
+    /// signal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.register_command("exit", Box::new(|app: &mut TerminalApp, _: &[&str]| {
+    ///         app.should_exit = true;
+    ///         String::new()
+    ///     }));
+    ///     app.set_exit_guard(|_app| false);
+    ///
+    ///     let ctrl_d = Event::Key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+    ///     assert!(!app.process_event(ctrl_d.clone()).await.unwrap());
+    ///     app.current_input = "exit".to_string();
+    ///     assert!(!app.handle_enter_key("> ").await.unwrap());
+    ///     assert!(!app.should_exit);
+    ///
+    ///     app.clear_exit_guard();
+    ///     assert!(app.process_event(ctrl_d).await.unwrap());
+    /// }
+    /// ```
+    pub fn set_exit_guard<F>(&mut self, guard: F)
+    where
+        F: Fn(&TerminalApp) -> bool + Send + Sync + 'static,
+    {
+        self.exit_guard = Some(Box::new(guard));
+    }
+
+    /// Removes the exit guard.
+    pub fn clear_exit_guard(&mut self) {
+        self.exit_guard = None;
+    }
+
+    /// Consults the exit guard, cancelling the exit if it refuses.
+    fn exit_allowed(&mut self) -> bool {
+        let Some(guard) = self.exit_guard.take() else {
+            return true;
+        };
+        let allowed = guard(self);
+        if self.exit_guard.is_none() {
+            self.exit_guard = Some(guard);
+        }
+        if !allowed {
+            self.should_exit = false;
+            self.print_log_entry(&get_warn!(
+                "Exit cancelled by the exit guard",
+                "Daemon Console"
+            ));
+        }
+        allowed
     }
 
     /// Handles Ctrl+C key press with double-press confirmation.
     ///
-    /// The first press clears input, the second press within 5 seconds exits.
+    /// The first press clears input, the second press within 5 seconds exits. With
+    /// [`set_ctrl_c_cancels_commands`](Self::set_ctrl_c_cancels_commands) enabled, a
+    /// press while async commands run cancels them instead.
     ///
     /// # Returns
     ///
     /// Tuple of (should_quit, message_to_display)
     pub async fn handle_ctrl_c(&mut self) -> Result<(bool, String), Box<dyn std::error::Error>> {
+        if self.ctrl_c_cancels_commands {
+            let cancelled = self.cancel_running_commands();
+            if cancelled > 0 {
+                self.last_ctrl_c = None;
+                return Ok((
+                    false,
+                    get_warn!(
+                        &format!("Cancelled {} running command(s).", cancelled),
+                        "Daemon Console"
+                    ),
+                ));
+            }
+        }
         if !self.current_input.is_empty() {
             self.current_input.clear();
             self.cursor_position = 0;
@@ -607,34 +2755,51 @@ impl TerminalApp {
     }
 
     /// Handles up the arrow key press for command history navigation.
+    ///
+    /// Starting navigation remembers the current input as a prefix filter.
     fn handle_up_key(&mut self) {
-        if self.command_history.is_empty() {
-            return;
-        }
-        let new_index = match self.history_index {
-            Some(idx) if idx > 0 => idx - 1,
-            Some(_) => return,
-            None => self.command_history.len() - 1,
+        let end = match self.history_index {
+            Some(idx) => idx,
+            None => {
+                self.history_prefix = self.current_input.clone();
+                self.command_history.len()
+            }
         };
-        self.history_index = Some(new_index);
-        self.current_input = self.command_history[new_index].clone();
-        self.cursor_position = self.current_input.chars().count();
+        let prefix = &self.history_prefix;
+        if let Some(idx) = self.command_history[..end.min(self.command_history.len())]
+            .iter()
+            .rposition(|entry| entry.starts_with(prefix.as_str()))
+        {
+            self.show_history_entry(idx);
+        }
     }
 
     /// Handles down the arrow key press for command history navigation.
     fn handle_down_key(&mut self) {
-        let new_index = match self.history_index {
-            Some(idx) if idx < self.command_history.len() - 1 => idx + 1,
-            Some(_) => {
+        let Some(current) = self.history_index else {
+            return;
+        };
+        let prefix = &self.history_prefix;
+        match self
+            .command_history
+            .get(current + 1..)
+            .unwrap_or_default()
+            .iter()
+            .position(|entry| entry.starts_with(prefix.as_str()))
+        {
+            Some(offset) => self.show_history_entry(current + 1 + offset),
+            None => {
                 self.history_index = None;
-                self.current_input.clear();
-                self.cursor_position = 0;
-                return;
+                self.current_input = std::mem::take(&mut self.history_prefix);
+                self.cursor_position = self.current_input.chars().count();
             }
-            None => return,
-        };
-        self.history_index = Some(new_index);
-        self.current_input = self.command_history[new_index].clone();
+        }
+    }
+
+    /// Puts a history entry into the input line.
+    fn show_history_entry(&mut self, idx: usize) {
+        self.history_index = Some(idx);
+        self.current_input = self.command_history[idx].clone();
         self.cursor_position = self.current_input.chars().count();
     }
 
@@ -643,64 +2808,359 @@ impl TerminalApp {
         &mut self,
         input_prefix: &str,
     ) -> Result<bool, Box<dyn std::error::Error>> {
-        if !self.current_input.trim().is_empty() {
-            self.command_history.push(self.current_input.clone());
+        self.refresh_validation();
+        if self.block_invalid_submit && self.input_error().is_some() {
+            self.render_input_line()?;
+        } else if !self.current_input.trim().is_empty() {
+            let line = self.current_input.clone();
+            self.core.push_history(line.clone());
+            self.submit_line(&line, input_prefix).await?;
+        } else if let Some(line) = self.empty_enter_line() {
+            self.submit_line(&line, input_prefix).await?;
+        } else {
             self.clear_input_line();
-            writeln!(self.stdout_handle, "{}{}", input_prefix, self.current_input)?;
-            let input_copy = self.current_input.clone();
+            self.render_input_line()?;
+        }
+        Ok(self.should_exit && self.exit_allowed())
+    }
+
+    /// Returns the line to run for an empty Enter, if any.
+    fn empty_enter_line(&self) -> Option<String> {
+        if self.has_running_commands() {
+            return None;
+        }
+        match &self.on_empty_enter {
+            EmptyEnterAction::Nothing => None,
+            EmptyEnterAction::RepeatLast => self.command_history.last().cloned(),
+            EmptyEnterAction::Run(line) => Some(line.clone()),
+        }
+    }
+
+    /// Prints a submitted line after the prompt it was typed at.
+    fn echo_line(&mut self, line: &str, input_prefix: &str) -> std::io::Result<()> {
+        if self.multiline_prompt {
+            writeln!(self.stdout_handle, "{}", input_prefix)?;
+            queue!(self.stdout_handle, cursor::MoveToColumn(0))?;
+            writeln!(
+                self.stdout_handle,
+                "{}",
+                command::highlight_command_line(line)
+            )
+        } else {
+            writeln!(
+                self.stdout_handle,
+                "{}{}",
+                input_prefix,
+                command::highlight_command_line(line)
+            )
+        }
+    }
+
+    /// Echoes a submitted line, runs it and resets the input line.
+    async fn submit_line(
+        &mut self,
+        line: &str,
+        input_prefix: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.clear_input_line();
+        if self.echo_input {
+            self.echo_line(line, input_prefix)?;
+        }
+        if self.events_wanted() {
             self.emit_events(events::DaemonConsoleEvent::UserConsoleInput {
-                raw: input_copy.clone(),
+                raw: line.to_string(),
                 timestamp: events::DaemonConsoleEvent::now_ts(),
             });
-            let command_output = command::execute_command(self, &input_copy).await;
-            if !command_output.is_empty() {
-                for line in command_output.lines() {
-                    execute!(self.stdout_handle, cursor::MoveToColumn(0))?;
-                    writeln!(self.stdout_handle, "{}", line.trim_start())?;
+        }
+        if let Some(level) = self.echo_commands_as_log {
+            let module = self.default_module.clone();
+            let entry = format!("command: {}", line);
+            self.logger(level, &entry, module.as_deref(), None);
+        }
+        if line.trim() == ".." && !self.contexts.is_empty() {
+            self.contexts.pop();
+        } else if let Some(output) = self.evaluate_line(line) {
+            self.run_submitted_command(line, Some(output)).await?;
+        } else {
+            let mut failed = false;
+            for (link, segment) in parse::split_chain(line) {
+                // A skipped `&&` command leaves the chain failed for the next link
+                if link == parse::ChainLink::OnSuccess && failed {
+                    continue;
                 }
-            } else {
-                writeln!(self.stdout_handle)?;
+                let segment = self.scoped_command(segment);
+                failed = !self.run_submitted_command(&segment, None).await?;
             }
-            self.current_input.clear();
-            self.cursor_position = 0;
-            self.history_index = None;
-            self.render_input_line()?;
-        } else {
-            self.clear_input_line();
-            self.render_input_line()?;
         }
-        Ok(self.should_exit)
+        self.current_input.clear();
+        self.cursor_position = 0;
+        self.history_index = None;
+        self.input_template = false;
+        self.render_input_line()?;
+        Ok(())
+    }
+
+    /// Parses redirection and substitutions of one command and executes it.
+    async fn dispatch_submitted(
+        &mut self,
+        command_line: &str,
+    ) -> (String, Option<parse::Redirection>) {
+        match parse::split_redirection(command_line) {
+            Ok((command_line, redirection)) => {
+                match command::expand_substitutions(self, command_line) {
+                    Ok(expanded) => (
+                        command::dispatch_command(self, &expanded, false).await,
+                        redirection,
+                    ),
+                    Err(e) => {
+                        self.mark_command_failed();
+                        (get_error!(&e, "CommandStatus"), None)
+                    }
+                }
+            }
+            Err(e) => {
+                self.mark_command_failed();
+                (get_error!(&e, "CommandStatus"), None)
+            }
+        }
+    }
+
+    /// Runs the line evaluator, if one is set, on a submitted line.
+    fn evaluate_line(&mut self, line: &str) -> Option<String> {
+        let evaluator = self.line_evaluator.take()?;
+        let output = evaluator(self, line);
+        // Keep a replacement the evaluator may have installed
+        if self.line_evaluator.is_none() {
+            self.line_evaluator = Some(evaluator);
+        }
+        output
+    }
+
+    /// Runs one command from a submitted line and prints its output.
+    ///
+    /// `evaluated` carries output the line evaluator already produced, in which
+    /// case nothing is dispatched. Returns whether the command succeeded, which
+    /// decides `&&` chains.
+    async fn run_submitted_command(
+        &mut self,
+        command_line: &str,
+        evaluated: Option<String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.command_failed = false;
+        let started = Instant::now();
+        let correlation_id = events::DaemonConsoleEvent::new_correlation_id();
+        if self.events_wanted() {
+            self.emit_events(events::DaemonConsoleEvent::CommandStarted {
+                command: command_line.to_string(),
+                correlation_id: correlation_id.clone(),
+                timestamp: events::DaemonConsoleEvent::now_ts(),
+            });
+        }
+        self.current_correlation_id = Some(correlation_id.clone());
+        let (command_output, redirection) = match evaluated {
+            Some(output) => (output, None),
+            None => self.dispatch_submitted(command_line).await,
+        };
+        // An async command takes the id with it and reports completion later
+        let finished = self.current_correlation_id.take().is_some();
+        if finished && self.events_wanted() {
+            self.emit_events(events::DaemonConsoleEvent::CommandCompleted {
+                command: command_line.to_string(),
+                output: command_output.clone(),
+                correlation_id: correlation_id.clone(),
+                timestamp: events::DaemonConsoleEvent::now_ts(),
+            });
+        }
+        self.last_output = Some(command_output.clone());
+        if finished {
+            self.publish_command_result(
+                CommandResult::new(command_line, command_output.clone())
+                    .with_correlation_id(correlation_id.clone())
+                    .with_duration(started.elapsed()),
+            );
+        }
+        let command_output = match redirection {
+            Some(redirection) if finished => {
+                let printed = Self::redirect_output(&redirection, &command_output);
+                if !printed.is_empty() {
+                    self.mark_command_failed();
+                }
+                printed
+            }
+            Some(redirection) => {
+                self.redirections.insert(correlation_id, redirection);
+                command_output
+            }
+            None => command_output,
+        };
+        self.render_paged_output(command_line, &command_output);
+        Ok(self.last_command_succeeded())
     }
 
     /// Handles character input by inserting at the cursor position.
     fn handle_char_input(&mut self, c: char) {
+        if self.input_template {
+            self.take_placeholder_at_cursor();
+        }
         let char_count = self.current_input.chars().count();
 
         if self.cursor_position > char_count {
             self.cursor_position = char_count;
         }
 
+        if let Some(max) = self.max_input_len {
+            if char_count >= max {
+                if !self.input_limit_warned {
+                    self.input_limit_warned = true;
+                    self.warn(&format!("Input is limited to {} characters", max));
+                }
+                return;
+            }
+            self.input_limit_warned = false;
+        }
+
         let mut chars: Vec<char> = self.current_input.chars().collect();
         chars.insert(self.cursor_position, c);
         self.current_input = chars.into_iter().collect();
         self.cursor_position += 1;
     }
 
-    /// Dispatches log events if event dispatching is enabled
-    fn dispatch_log_events(&mut self, message: &str, level: LogLevel) {
-        if self.dispatch_event {
-            self.emit_events(events::DaemonConsoleEvent::TerminalLog {
-                level,
-                message: message.to_string(),
-                module_name: Some("Stream".into()),
-                timestamp: events::DaemonConsoleEvent::now_ts(),
-            });
+    /// Applies one action received from the action channel.
+    ///
+    /// Logs requested through actions never dispatch `TerminalLog` events. Event
+    /// subscribers log back into the console through the action channel, so this
+    /// guarantees that a subscriber reacting to `TerminalLog` cannot trigger itself
+    /// again and amplify into an endless loop.
+    fn apply_action(&mut self, action: AppAction) {
+        match action {
+            AppAction::RegisterCommand(name, handler) => {
+                self.register_command(name, handler);
+            }
+            AppAction::RegisterAsyncCommand(name, handler) => {
+                self.register_async_command(name, handler);
+            }
+            AppAction::Info(_)
+            | AppAction::Debug(_)
+            | AppAction::Warn(_)
+            | AppAction::Error(_)
+            | AppAction::Critical(_) => {
+                self.handle_log_action(action);
+            }
+            AppAction::Logger(level, message, module_name, dispatch_event) => {
+                self.handle_logger_action(level, message, module_name, dispatch_event);
+            }
+            AppAction::Emit(event) => self.emit_events(event),
+        }
+    }
+
+    /// Applies every action currently queued on the action channel.
+    ///
+    /// `run` does this on each loop iteration; call it directly when driving the
+    /// app headlessly. Returns the number of actions applied, or 0 while `run` owns
+    /// the receiver.
+    ///
+    /// # Examples
+    ///
+    /// A subscriber that logs every `TerminalLog` event it sees does not loop:
+    ///
+    /// ```
+    /// use daemon_console::{AppAction, ConsoleOutput, TerminalApp, events::DaemonConsoleEvent};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// let handle = app.get_action_sender();
+    /// let mut events = app.subscribe_events().unwrap();
+    ///
+    /// app.info("seed");
+    /// let mut rounds = 0;
+    /// while let Ok(event) = events.try_recv() {
+    ///     if let DaemonConsoleEvent::TerminalLog { message, .. } = event {
+    ///         handle.try_send(AppAction::Debug(format!("saw: {message}"))).unwrap();
+    ///     }
+    ///     app.process_pending_actions();
+    ///     rounds += 1;
+    ///     assert!(rounds < 10, "event feedback loop");
+    /// }
+    /// assert_eq!(rounds, 1);
+    /// ```
+    pub fn process_pending_actions(&mut self) -> usize {
+        let Some(mut action_rx) = self.action_receiver.take() else {
+            return 0;
+        };
+        let mut applied = 0;
+        while let Some(action) = action_rx.try_recv() {
+            self.apply_action(action);
+            applied += 1;
+        }
+        self.action_receiver = Some(action_rx);
+        applied
+    }
+
+    /// Prints the results of finished async commands without running the event loop.
+    ///
+    /// `run` does this on every iteration; call it directly when driving the app
+    /// yourself, e.g. in tests. Returns how many results were handled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing a result fails.
+    ///
+    /// # Examples
+    ///
+    /// Indentation in async output is kept as-is:
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use daemon_console::{AsyncCommandHandler, ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// struct Tree;
+    ///
+    /// #[async_trait]
+    /// impl AsyncCommandHandler for Tree {
+    ///     async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+    ///         "root\n  child\n    leaf".to_string()
+    ///     }
+    ///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+    ///         Box::new(Tree)
+    ///     }
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     app.register_async_command("tree", Box::new(Tree));
+    ///     daemon_console::command::execute_command(&mut app, "tree").await;
+    ///
+    ///     while app.process_command_results().await.unwrap() == 0 {
+    ///         tokio::task::yield_now().await;
+    ///     }
+    ///     let text = captured.contents();
+    ///     assert!(text.lines().any(|line| line.ends_with("  child")));
+    ///     assert!(text.lines().any(|line| line.ends_with("    leaf")));
+    /// }
+    /// ```
+    pub async fn process_command_results(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let Some(mut result_rx) = self.command_result_rx.take() else {
+            return Ok(0);
+        };
+        let mut handled = 0;
+        let mut outcome = Ok(());
+        while let Ok(result) = result_rx.try_recv() {
+            handled += 1;
+            outcome = self.handle_command_result(result).await;
+            if outcome.is_err() {
+                break;
+            }
         }
+        self.command_result_rx = Some(result_rx);
+        outcome.map(|()| handled)
     }
 
-    /// Handles log actions with event dispatch toggling
+    /// Handles log actions without dispatching log events
     fn handle_log_action(&mut self, action: AppAction) {
-        self.switch_if_dispatch_event();
+        self.suppress_log_events();
         match action {
             AppAction::Info(msg) => self.info(&msg),
             AppAction::Debug(msg) => self.debug(&msg),
@@ -709,10 +3169,10 @@ impl TerminalApp {
             AppAction::Critical(msg) => self.critical(&msg),
             _ => {} // Should not reach here
         }
-        self.switch_if_dispatch_event();
+        self.resume_log_events();
     }
 
-    /// Handles logger actions with event dispatch toggling
+    /// Handles logger actions without dispatching log events
     fn handle_logger_action(
         &mut self,
         level: LogLevel,
@@ -720,10 +3180,16 @@ impl TerminalApp {
         module_name: Option<String>,
         dispatch_event: Option<bool>,
     ) {
-        self.switch_if_dispatch_event();
+        self.suppress_log_events();
         let module_str = module_name.as_deref();
         self.logger(level, &message, module_str, dispatch_event);
-        self.switch_if_dispatch_event();
+        self.resume_log_events();
+    }
+
+    /// Logs a message under the app's default module name.
+    fn log_with_default_module(&mut self, level: LogLevel, message: &str) {
+        let module = self.default_module.clone();
+        self.logger(level, message, module.as_deref(), None);
     }
 
     /// Log info-level messages.
@@ -747,7 +3213,7 @@ impl TerminalApp {
     /// }
     /// ```
     pub fn info(&mut self, message: &str) {
-        self.logger(LogLevel::Info, message, Some("Stream"), None);
+        self.log_with_default_module(LogLevel::Info, message);
     }
 
     /// Log debug-level messages.
@@ -764,7 +3230,7 @@ impl TerminalApp {
     /// }
     /// ```
     pub fn debug(&mut self, message: &str) {
-        self.logger(LogLevel::Debug, message, Some("Stream"), None);
+        self.log_with_default_module(LogLevel::Debug, message);
     }
 
     /// Log warn-level messages.
@@ -781,7 +3247,7 @@ impl TerminalApp {
     /// }
     /// ```
     pub fn warn(&mut self, message: &str) {
-        self.logger(LogLevel::Warn, message, Some("Stream"), None);
+        self.log_with_default_module(LogLevel::Warn, message);
     }
 
     /// Log error-level messages.
@@ -798,7 +3264,7 @@ impl TerminalApp {
     /// }
     /// ```
     pub fn error(&mut self, message: &str) {
-        self.logger(LogLevel::Error, message, Some("Stream"), None);
+        self.log_with_default_module(LogLevel::Error, message);
     }
 
     /// Log critical-level messages.
@@ -815,7 +3281,57 @@ impl TerminalApp {
     /// }
     /// ```
     pub fn critical(&mut self, message: &str) {
-        self.logger(LogLevel::Critical, message, Some("Stream"), None);
+        self.log_with_default_module(LogLevel::Critical, message);
+    }
+
+    /// Log info-level messages under `module` instead of the default module.
+    ///
+    /// Useful when one console hosts several subsystems. `None` logs without a
+    /// module prefix. The emitted `TerminalLog` event carries the same module.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::events::DaemonConsoleEvent;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// let mut events = app.subscribe_events().unwrap();
+    ///
+    /// app.info_with(Some("Database"), "Connected");
+    /// app.info("Ready");
+    ///
+    /// let modules: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+    ///     .filter_map(|event| match event {
+    ///         DaemonConsoleEvent::TerminalLog { module_name, .. } => Some(module_name),
+    ///         _ => None,
+    ///     })
+    ///     .collect();
+    /// assert_eq!(modules, [Some("Database".to_string()), Some("Stream".to_string())]);
+    /// ```
+    pub fn info_with(&mut self, module: Option<&str>, message: &str) {
+        self.logger(LogLevel::Info, message, module, None);
+    }
+
+    /// Log debug-level messages under `module`; see [`info_with`](Self::info_with).
+    pub fn debug_with(&mut self, module: Option<&str>, message: &str) {
+        self.logger(LogLevel::Debug, message, module, None);
+    }
+
+    /// Log warn-level messages under `module`; see [`info_with`](Self::info_with).
+    pub fn warn_with(&mut self, module: Option<&str>, message: &str) {
+        self.logger(LogLevel::Warn, message, module, None);
+    }
+
+    /// Log error-level messages under `module`; see [`info_with`](Self::info_with).
+    pub fn error_with(&mut self, module: Option<&str>, message: &str) {
+        self.logger(LogLevel::Error, message, module, None);
+    }
+
+    /// Log critical-level messages under `module`; see [`info_with`](Self::info_with).
+    pub fn critical_with(&mut self, module: Option<&str>, message: &str) {
+        self.logger(LogLevel::Critical, message, module, None);
     }
 
     /// Unified logger method that allows specifying a custom module name for the log message.
@@ -827,6 +3343,8 @@ impl TerminalApp {
     /// * `module_name` - The name of the module to associate with the log message (optional)
     /// * `dispatch_event` - Whether to dispatch log events (optional, defaults to true)
     ///
+    /// Messages below [`logger::min_level`] are neither printed nor dispatched.
+    ///
     /// # Examples
     ///
     /// ```
@@ -845,47 +3363,69 @@ impl TerminalApp {
         module_name: Option<&str>,
         dp_evt: Option<bool>,
     ) {
-        let formatted_message = match level {
-            LogLevel::Info => {
-                if let Some(module) = module_name {
-                    get_info!(message, module)
-                } else {
-                    get_info!(message)
-                }
-            }
-            LogLevel::Warn => {
-                if let Some(module) = module_name {
-                    get_warn!(message, module)
-                } else {
-                    get_warn!(message)
-                }
-            }
-            LogLevel::Error => {
-                if let Some(module) = module_name {
-                    get_error!(message, module)
-                } else {
-                    get_error!(message)
-                }
-            }
-            LogLevel::Debug => {
-                if let Some(module) = module_name {
-                    get_debug!(message, module)
-                } else {
-                    get_debug!(message)
-                }
-            }
-            LogLevel::Critical => {
-                if let Some(module) = module_name {
-                    get_critical!(message, module)
-                } else {
-                    get_critical!(message)
-                }
-            }
+        let print = !self.quiet_hides(level);
+        self.log_entry(level, message, module_name, dp_evt.unwrap_or(true), print);
+    }
+
+    /// Logs a message to the log file and event subscribers without printing it.
+    ///
+    /// For audit trails consumed only by subscribers, which would clutter the
+    /// terminal. Messages below [`logger::min_level`] are dropped, like with
+    /// [`logger`](Self::logger).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::events::DaemonConsoleEvent;
+    /// use daemon_console::logger::LogLevel;
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// let captured = OutputBuffer::new();
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    /// let mut events = app.subscribe_events().unwrap();
+    ///
+    /// app.log_silent(LogLevel::Info, "user alice logged in", Some("Audit"));
+    ///
+    /// assert!(matches!(
+    ///     events.try_recv(),
+    ///     Ok(DaemonConsoleEvent::TerminalLog { message, .. }) if message == "user alice logged in"
+    /// ));
+    /// assert!(captured.is_empty());
+    /// ```
+    pub fn log_silent(&mut self, level: LogLevel, message: &str, module_name: Option<&str>) {
+        self.log_entry(level, message, module_name, true, false);
+    }
+
+    /// Writes a log entry to the enabled sinks.
+    fn log_entry(
+        &mut self,
+        level: LogLevel,
+        message: &str,
+        module_name: Option<&str>,
+        dispatch: bool,
+        print: bool,
+    ) {
+        if !logger::level_enabled(level) {
+            return;
+        }
+        let Some(message) = logger::filter_message(level, message, module_name) else {
+            return;
         };
-        self.print_log_entry(&formatted_message);
-        let should_dispatch = dp_evt.unwrap_or(true);
-        if should_dispatch {
-            self.dispatch_log_events(message, level);
+        let message = message.as_ref();
+        let formatted_message = logger::format_filtered_message(level, message, module_name);
+        if self.log_file.is_some() {
+            let plain = logger::format_multiline_plain(level, message, module_name);
+            self.write_log_file(&plain);
+        }
+        #[cfg(feature = "serde")]
+        self.write_json_log(Some(level), message, module_name);
+        if print {
+            self.print_terminal_entry(&formatted_message);
+        }
+        self.metrics.record_log(level);
+        if dispatch {
+            self.dispatch_log_events(message, level, module_name);
         };
     }
 
@@ -894,67 +3434,278 @@ impl TerminalApp {
         &mut self,
         result: CommandResult,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if !result.output.is_empty() {
-            for line in result.output.lines() {
-                self.print_log_entry(line.trim_start());
-            }
+        let redirection = result
+            .correlation_id
+            .as_ref()
+            .and_then(|id| self.redirections.remove(id));
+        let printed = match redirection {
+            Some(redirection) => Self::redirect_output(&redirection, &result.output),
+            None => result.output.clone(),
+        };
+        self.render_paged_output(&result.command, &printed);
+        if let Some(correlation_id) = &result.correlation_id
+            && self.events_wanted()
+        {
+            self.emit_events(events::DaemonConsoleEvent::CommandCompleted {
+                command: result.command.clone(),
+                output: result.output.clone(),
+                correlation_id: correlation_id.clone(),
+                timestamp: events::DaemonConsoleEvent::now_ts(),
+            });
         }
+        self.last_output = Some(result.output.clone());
+        if let Some(duration) = result.duration {
+            self.record_command_run(&result.command, duration);
+        }
+        self.publish_command_result(result);
         Ok(())
     }
 
-    /// Checks for completed running commands and cleans up finished tasks
-    async fn check_running_commands(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut completed_indices = Vec::new();
+    /// Prints command output line by line, keeping the input line intact.
+    ///
+    /// Used for both sync and async commands so they render the same way: trailing
+    /// newlines and blank lines are dropped, so empty output prints nothing and
+    /// output ending in `\n` prints no extra blank line. Blank lines inside the
+    /// output are kept, and indentation is preserved since output is often
+    /// structured (trees, tables). Every line also goes to the log file.
+    ///
+    /// # Examples
+    ///
+    /// Sync and async commands with the same output print the same lines, however
+    /// many trailing newlines they end with:
+    ///
+    /// ```
+    /// use async_trait::async_trait;
+    /// use daemon_console::command::execute_command;
+    /// use daemon_console::{AsyncCommandHandler, ConsoleOutput, OutputBuffer, TerminalApp, utils};
+    ///
+    /// const TREE: &str = "root\n  child\n\n    leaf";
+    ///
+    /// struct Tree;
+    ///
+    /// #[async_trait]
+    /// impl AsyncCommandHandler for Tree {
+    ///     async fn execute_async(&mut self, _app: &mut TerminalApp, _args: &[&str]) -> String {
+    ///         format!("{TREE}\n\n\n")
+    ///     }
+    ///     fn box_clone(&self) -> Box<dyn AsyncCommandHandler> {
+    ///         Box::new(Tree)
+    ///     }
+    /// }
+    ///
+    /// async fn printed(app: &mut TerminalApp, output: &OutputBuffer, line: &str) -> String {
+    ///     output.clear();
+    ///     let returned = execute_command(app, line).await;
+    ///     app.render_command_output(&returned);
+    ///     while !app.running_jobs().is_empty() {
+    ///         tokio::task::yield_now().await;
+    ///     }
+    ///     app.process_command_results().await.unwrap();
+    ///     utils::strip_ansi(&output.contents())
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     // With no prompt and no input, redraws leave only the rendered output
+    ///     let output = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(output.clone()));
+    ///     app.set_prompt("");
+    ///     app.register_command("tree", Box::new(|_: &mut TerminalApp, _: &[&str]| format!("{TREE}\n")));
+    ///     app.register_command("empty", Box::new(|_: &mut TerminalApp, _: &[&str]| "\n".to_string()));
+    ///     app.register_async_command("atree", Box::new(Tree));
+    ///     app.set_command_quiet_start("atree", true);
+    ///
+    ///     let sync = printed(&mut app, &output, "tree").await;
+    ///     let async_ = printed(&mut app, &output, "atree").await;
+    ///     assert_eq!(sync, format!("{TREE}\n"));
+    ///     assert_eq!(sync, async_);
+    ///     assert_eq!(printed(&mut app, &output, "empty").await, "");
+    /// }
+    /// ```
+    pub fn render_command_output(&mut self, output: &str) {
+        for line in output.trim_end().lines() {
+            self.print_output_line(line);
+        }
+    }
+
+    /// Sets how `\r` in command output is rendered.
+    ///
+    /// Tools wrapping progress bars emit `\r` to redraw a line; printed as is, it
+    /// moves the cursor mid-entry and garbles the console. Defaults to
+    /// [`CarriageReturnMode::Strip`]. `\r\n` line endings are always treated as
+    /// plain newlines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{CarriageReturnMode, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// let stripped = app.capture_output(|app| app.render_command_output("50%\r100%\n"));
+    /// assert_eq!(stripped, "50%100%\n");
+    ///
+    /// app.set_carriage_return_mode(CarriageReturnMode::Overwrite);
+    /// let overwritten = app.capture_output(|app| app.render_command_output("50%\r100%\n"));
+    /// assert_eq!(overwritten, "100%\n");
+    /// let partial = app.capture_output(|app| app.render_command_output("loading...\rdone"));
+    /// assert_eq!(partial, "doneing...\n");
+    /// ```
+    pub fn set_carriage_return_mode(&mut self, mode: CarriageReturnMode) {
+        self.carriage_return_mode = mode;
+    }
+
+    /// Sets how control characters in command output are rendered.
+    ///
+    /// Output of external tools may contain escape sequences that clear the
+    /// screen or move the cursor, which would let a subprocess hijack the
+    /// console. Defaults to [`ControlCharMode::Sanitize`], which keeps colors and
+    /// hyperlinks but neutralizes everything else. Carriage returns are resolved
+    /// first, see [`set_carriage_return_mode`](Self::set_carriage_return_mode).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ControlCharMode, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// let cleared = app.capture_output(|app| app.render_command_output("\x1b[2Jgotcha"));
+    /// assert_eq!(cleared, "^[[2Jgotcha\n");
+    /// let colored = app.capture_output(|app| app.render_command_output("\x1b[31mred\x1b[0m"));
+    /// assert_eq!(colored, "\x1b[31mred\x1b[0m\n");
+    /// let link = daemon_console::logger::hyperlink("docs", "https://docs.rs");
+    /// assert_eq!(app.capture_output(|app| app.render_command_output(&link)), link + "\n");
+    ///
+    /// app.set_control_char_mode(ControlCharMode::ExpandTabs);
+    /// let table = app.capture_output(|app| app.render_command_output("id\tname"));
+    /// assert_eq!(table, "id      name\n");
+    ///
+    /// app.set_control_char_mode(ControlCharMode::PassThrough);
+    /// let raw = app.capture_output(|app| app.render_command_output("\x1b[2J"));
+    /// assert_eq!(raw, "\x1b[2J\n");
+    /// ```
+    pub fn set_control_char_mode(&mut self, mode: ControlCharMode) {
+        self.control_char_mode = mode;
+    }
+
+    /// Colors command output lines by content, e.g. lines containing `ERROR` in red.
+    ///
+    /// The highlighter is asked for a color for each output line of sync and async
+    /// commands; `None` leaves the line as is. Lines that already contain escape
+    /// sequences are never recolored, and nothing is colored while colors are
+    /// disabled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,standalone_crate
+    /// use crossterm::style::{Color, SetForegroundColor};
+    /// use daemon_console::TerminalApp;
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output_highlighter(|line| line.contains("ERROR").then_some(Color::Red));
+    ///
+    /// let text = app.capture_output(|app| app.render_command_output("ok\nERROR: disk full"));
+    /// let red = SetForegroundColor(Color::Red).to_string();
+    /// assert!(text.starts_with("ok\n"));
+    /// assert!(text.contains(&format!("{}ERROR: disk full", red)));
+    /// ```
+    pub fn set_output_highlighter<F>(&mut self, highlighter: F)
+    where
+        F: Fn(&str) -> Option<crossterm::style::Color> + Send + Sync + 'static,
+    {
+        self.output_highlighter = Some(Box::new(highlighter));
+    }
+
+    /// Removes the output highlighter.
+    pub fn clear_output_highlighter(&mut self) {
+        self.output_highlighter = None;
+    }
 
-        for (i, cmd) in self.running_commands.iter().enumerate() {
-            if cmd.handle.is_finished() {
-                // Log completion (using the command field)
-                let _ = &cmd.command;
-                completed_indices.push(i);
+    /// Prints one command output line, resolving carriage returns, neutralizing
+    /// control characters and coloring it with the output highlighter.
+    pub(crate) fn print_output_line(&mut self, line: &str) {
+        let resolved;
+        let line = if line.contains('\r') {
+            resolved = utils::resolve_carriage_returns(line, self.carriage_return_mode);
+            resolved.as_str()
+        } else {
+            line
+        };
+        let sanitized;
+        let line = match self.control_char_mode {
+            ControlCharMode::PassThrough => line,
+            mode => {
+                sanitized =
+                    utils::sanitize_control_chars(line, mode == ControlCharMode::ExpandTabs);
+                sanitized.as_str()
+            }
+        };
+        let color = match &self.output_highlighter {
+            Some(highlighter) if logger::color_enabled() && !line.contains('\x1b') => {
+                highlighter(line)
             }
+            _ => None,
+        };
+        match color {
+            Some(color) => self.print_log_entry(&format!(
+                "{}{}{}",
+                crossterm::style::SetForegroundColor(color),
+                line,
+                crossterm::style::ResetColor
+            )),
+            None => self.print_log_entry(line),
         }
+    }
 
-        // Remove completed commands in reverse order to maintain indices
-        for &i in completed_indices.iter().rev() {
-            self.running_commands.remove(i);
+    /// Writes output to a redirection target, returning what to print instead.
+    fn redirect_output(redirection: &parse::Redirection, output: &str) -> String {
+        match redirection.write(output) {
+            Ok(()) => String::new(),
+            Err(e) => get_error!(
+                &format!("Cannot write to '{}': {}", redirection.path, e),
+                "CommandStatus"
+            ),
         }
-
-        Ok(())
     }
 
     async fn spawn_async_command(
         &mut self,
         command: String,
+        args: Vec<String>,
         mut handler: Box<dyn AsyncCommandHandler>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        let args = if parts.len() > 1 { &parts[1..] } else { &[] };
-        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
-        let tx = self.command_result_tx.as_ref().unwrap().clone();
+        let Some(tx) = self.command_result_tx.clone() else {
+            return Err("async commands cannot be started from a detached console".into());
+        };
         let cmd_copy = command.clone();
+        let correlation_id = self.current_correlation_id.take();
         // Clone action_sender to pass to the async command
         let action_sender = self.action_sender.clone();
 
-        let handle = tokio::spawn(async move {
-            // Create a temporary app instance for the async command
-            let mut temp_app = TerminalApp::new();
-            // Set the action sender for the temporary app
-            if let Some(sender) = action_sender {
-                temp_app.set_action_sender(sender);
-            }
+        let started = Instant::now();
+        let cancel_token = CancellationToken::new();
+        let task_token = cancel_token.clone();
+        let handle = self.spawn_task(async move {
+            let context = command::CommandContext::new(Some(action_sender), task_token);
             let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-            let result = handler.execute_async(&mut temp_app, &args_refs).await;
+            let result = handler.execute_with_context(&context, &args_refs).await;
 
             let _ = tx.send(CommandResult {
-                command: cmd_copy,
-                output: result.clone(),
+                correlation_id,
+                ..CommandResult::new(cmd_copy, result.clone()).with_duration(started.elapsed())
             });
 
             result
         });
 
-        self.running_commands
-            .push(RunningCommand { command, handle });
+        self.running_commands.push(RunningCommand {
+            command,
+            handle,
+            tracked: false,
+            cancel_token,
+            cancel_requested: None,
+        });
+        self.metrics.async_spawned += 1;
 
         Ok(())
     }
@@ -2,9 +2,117 @@
 //!
 //! This module provides a logging system with different severity levels
 //! (Info, Warn, Error, Debug) and automatic timestamp formatting.
+//!
+//! All formatting configuration (color switch, minimum level) is global and lives
+//! here rather than on `TerminalApp`, so library crates can format log lines the
+//! same way without running the interactive console. The app only adds what needs
+//! a terminal or its own state: redrawing the prompt, the log file and the default
+//! module name.
 
-use chrono::Local;
+use chrono::{DateTime, Local};
 use crossterm::style::{self, Color, ResetColor, SetForegroundColor};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(true);
+static SHOW_TIMESTAMP: AtomicBool = AtomicBool::new(true);
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(0);
+static MODULE_SEPARATOR: RwLock<Cow<'static, str>> = RwLock::new(Cow::Borrowed("/"));
+static CLOCK: RwLock<Option<Clock>> = RwLock::new(None);
+static THEME: RwLock<Theme> = RwLock::new(Theme::DEFAULT);
+static LOG_FILTER: RwLock<Option<LogFilter>> = RwLock::new(None);
+
+thread_local! {
+    /// Set while the log filter runs on this thread, so its own logs bypass it.
+    static IN_FILTER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Rewrites or drops log messages before formatting, see [`set_log_filter`].
+pub type LogFilter =
+    Box<dyn Fn(LogLevel, &str, Option<&str>) -> Option<String> + Send + Sync + 'static>;
+
+/// Source of the time stamped on log lines, see [`set_clock`].
+pub type Clock = Box<dyn Fn() -> DateTime<Local> + Send + Sync + 'static>;
+
+/// Enables or disables ANSI colors and styles in all console output.
+///
+/// Colors are enabled by default. This is a global switch shared by the log
+/// formatter and the command echo.
+pub fn set_color_enabled(enabled: bool) {
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether ANSI colors and styles are currently enabled.
+pub fn color_enabled() -> bool {
+    COLOR_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Shows or omits the `[HH:MM:SS]` prefix of log lines.
+///
+/// Shown by default. Turning it off helps when another logger already stamps
+/// each line; the module, level and message are kept. Like the color switch,
+/// this is global and applies to every formatted line, including the log file.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use daemon_console::logger::{self, LogLevel};
+///
+/// logger::set_show_timestamp(false);
+/// assert_eq!(
+///     logger::log_message_plain(LogLevel::Info, "ready", Some("Stream")),
+///     "[Stream/INFO] ready"
+/// );
+///
+/// logger::set_show_timestamp(true);
+/// let line = logger::log_message_plain(LogLevel::Info, "ready", Some("Stream"));
+/// assert!(line.ends_with("] [Stream/INFO] ready"));
+/// assert_eq!(line.find(':'), Some(3));
+/// ```
+pub fn set_show_timestamp(show: bool) {
+    SHOW_TIMESTAMP.store(show, Ordering::Relaxed);
+}
+
+/// Returns whether log lines start with a timestamp.
+pub fn show_timestamp() -> bool {
+    SHOW_TIMESTAMP.load(Ordering::Relaxed)
+}
+
+/// Wraps text in an OSC 8 escape so terminals render it as a clickable link.
+///
+/// The escape is invisible, so [`strip_ansi`](crate::utils::strip_ansi) and
+/// [`display_width`](crate::utils::display_width) see only `text`. With colors
+/// disabled the URL is spelled out instead, since plain output cannot carry links.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::logger::{LogLevel, hyperlink, log_message};
+/// use daemon_console::utils::{display_width, strip_ansi};
+///
+/// let link = hyperlink("dashboard", "https://example.com/d/1");
+/// assert_eq!(link, "\x1b]8;;https://example.com/d/1\x1b\\dashboard\x1b]8;;\x1b\\");
+/// assert_eq!(strip_ansi(&link), "dashboard");
+/// assert_eq!(display_width(&link), 9);
+///
+/// let line = log_message(LogLevel::Info, &format!("See {}", link), None);
+/// assert!(line.contains(&link));
+/// assert!(strip_ansi(&line).ends_with("See dashboard"));
+/// ```
+pub fn hyperlink(text: &str, url: &str) -> String {
+    if !color_enabled() {
+        return if text == url {
+            url.to_string()
+        } else {
+            format!("{} ({})", text, url)
+        };
+    }
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
 
 /// Log level enumeration for categorizing log messages.
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -16,6 +124,375 @@ pub enum LogLevel {
     Critical,
 }
 
+impl LogLevel {
+    /// All levels from least to most severe.
+    pub const ALL: [LogLevel; 5] = [
+        LogLevel::Debug,
+        LogLevel::Info,
+        LogLevel::Warn,
+        LogLevel::Error,
+        LogLevel::Critical,
+    ];
+
+    /// Returns the rank of the level, `0` for debug up to `4` for critical.
+    pub fn severity(self) -> u8 {
+        match self {
+            LogLevel::Debug => 0,
+            LogLevel::Info => 1,
+            LogLevel::Warn => 2,
+            LogLevel::Error => 3,
+            LogLevel::Critical => 4,
+        }
+    }
+
+    /// Returns the level name as shown in log lines, e.g. `INFO`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Critical => "CRITICAL",
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned when parsing an unknown log level name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLogLevelError(pub String);
+
+impl fmt::Display for ParseLogLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown log level '{}', expected one of: debug, info, warn, error, critical",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseLogLevelError {}
+
+impl FromStr for LogLevel {
+    type Err = ParseLogLevelError;
+
+    /// Parses a level name case-insensitively; `warning` is accepted for `warn`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "critical" => Ok(LogLevel::Critical),
+            _ => Err(ParseLogLevelError(s.to_string())),
+        }
+    }
+}
+
+/// Sets the text placed between the module name and the level in log lines.
+///
+/// Defaults to `/`, giving `[module/LEVEL]`. The separator is inserted verbatim
+/// inside the brackets, so `"] ["` gives `[module] [LEVEL]` and `"::"` gives
+/// `[module::LEVEL]`. Lines without a module are unaffected.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use daemon_console::logger::{self, LogLevel};
+///
+/// logger::set_color_enabled(false);
+/// logger::set_module_separator("] [");
+/// let line = logger::log_message(LogLevel::Info, "ready", Some("db"));
+/// assert!(line.ends_with("[db] [INFO] ready"));
+/// ```
+pub fn set_module_separator(separator: &str) {
+    let mut current = MODULE_SEPARATOR.write().unwrap_or_else(|e| e.into_inner());
+    *current = Cow::Owned(separator.to_string());
+}
+
+/// Returns the text placed between the module name and the level.
+pub fn module_separator() -> String {
+    MODULE_SEPARATOR
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .to_string()
+}
+
+/// Colors of the level indicators in log lines.
+///
+/// Select one of the built-in themes with [`set_theme`]. Themes only matter while
+/// colors are enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// Name used to select the theme, e.g. with the `theme` built-in command.
+    pub name: &'static str,
+    pub info: Color,
+    pub warn: Color,
+    pub error: Color,
+    pub debug: Color,
+    pub critical: Color,
+}
+
+impl Theme {
+    /// The original colors: green, yellow, red, grey and purple.
+    pub const DEFAULT: Theme = Theme {
+        name: "default",
+        info: Color::Green,
+        warn: Color::Yellow,
+        error: Color::Red,
+        debug: Color::DarkGrey,
+        critical: Color::AnsiValue(5),
+    };
+
+    /// Bright colors that stay readable on dim or low-quality displays.
+    pub const HIGH_CONTRAST: Theme = Theme {
+        name: "high-contrast",
+        info: Color::White,
+        warn: Color::Yellow,
+        error: Color::Red,
+        debug: Color::Grey,
+        critical: Color::Magenta,
+    };
+
+    /// The Okabe-Ito palette, distinguishable with common color vision deficiencies.
+    pub const COLORBLIND: Theme = Theme {
+        name: "colorblind",
+        info: Color::Rgb {
+            r: 0,
+            g: 114,
+            b: 178,
+        },
+        warn: Color::Rgb {
+            r: 230,
+            g: 159,
+            b: 0,
+        },
+        error: Color::Rgb {
+            r: 213,
+            g: 94,
+            b: 0,
+        },
+        debug: Color::DarkGrey,
+        critical: Color::Rgb {
+            r: 204,
+            g: 121,
+            b: 167,
+        },
+    };
+
+    /// No colors; levels are told apart by their names and bold or italic text.
+    pub const MONOCHROME: Theme = Theme {
+        name: "monochrome",
+        info: Color::Reset,
+        warn: Color::Reset,
+        error: Color::Reset,
+        debug: Color::Reset,
+        critical: Color::Reset,
+    };
+
+    /// All built-in themes.
+    pub const BUILTIN: [Theme; 4] = [
+        Theme::DEFAULT,
+        Theme::HIGH_CONTRAST,
+        Theme::COLORBLIND,
+        Theme::MONOCHROME,
+    ];
+
+    /// Looks up a built-in theme by name, case-insensitively.
+    pub fn by_name(name: &str) -> Option<Theme> {
+        Theme::BUILTIN
+            .into_iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the color of a level's indicator.
+    pub fn color(&self, level: LogLevel) -> Color {
+        match level {
+            LogLevel::Info => self.info,
+            LogLevel::Warn => self.warn,
+            LogLevel::Error => self.error,
+            LogLevel::Debug => self.debug,
+            LogLevel::Critical => self.critical,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DEFAULT
+    }
+}
+
+/// Sets the color theme of log lines. Takes effect for lines formatted afterwards.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use crossterm::style::{Color, SetForegroundColor};
+/// use daemon_console::logger::{self, LogLevel, Theme};
+///
+/// logger::set_theme(Theme::by_name("colorblind").unwrap());
+/// let line = logger::log_message(LogLevel::Warn, "disk almost full", None);
+/// assert!(line.contains(&SetForegroundColor(Theme::COLORBLIND.warn).to_string()));
+/// assert_eq!(logger::theme(), Theme::COLORBLIND);
+/// ```
+pub fn set_theme(theme: Theme) {
+    *THEME.write().unwrap_or_else(|e| e.into_inner()) = theme;
+}
+
+/// Returns the current color theme.
+pub fn theme() -> Theme {
+    *THEME.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replaces the clock used for log line timestamps.
+///
+/// Defaults to the system's local time. Pinning the time makes formatted log
+/// lines deterministic in tests. The clock is global and may be swapped from any
+/// thread; [`reset_clock`] restores the system clock.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use chrono::{Local, TimeZone};
+/// use daemon_console::logger::{self, LogLevel};
+///
+/// logger::set_color_enabled(false);
+/// logger::set_clock(|| Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 5).unwrap());
+/// let line = logger::log_message(LogLevel::Info, "ready", None);
+/// assert_eq!(line, "[09:30:05] [INFO] ready");
+///
+/// logger::reset_clock();
+/// ```
+pub fn set_clock<F>(clock: F)
+where
+    F: Fn() -> DateTime<Local> + Send + Sync + 'static,
+{
+    *CLOCK.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(clock));
+}
+
+/// Restores the system clock for log line timestamps.
+pub fn reset_clock() {
+    *CLOCK.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Returns the current time according to the logger's clock.
+pub fn now() -> DateTime<Local> {
+    match CLOCK.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(clock) => clock(),
+        None => Local::now(),
+    }
+}
+
+/// Sets the minimum level the app logging methods print and dispatch.
+///
+/// Defaults to `LogLevel::Debug`, which lets everything through.
+pub fn set_min_level(level: LogLevel) {
+    MIN_LEVEL.store(level.severity(), Ordering::Relaxed);
+}
+
+/// Returns the current minimum log level.
+pub fn min_level() -> LogLevel {
+    let severity = MIN_LEVEL.load(Ordering::Relaxed);
+    LogLevel::ALL
+        .into_iter()
+        .find(|level| level.severity() == severity)
+        .unwrap_or(LogLevel::Debug)
+}
+
+/// Returns whether messages at `level` pass the minimum level filter.
+pub fn level_enabled(level: LogLevel) -> bool {
+    level.severity() >= MIN_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Installs a global filter that sees every log message before it is formatted.
+///
+/// The filter receives the level, message and module name. Returning `None`
+/// drops the entry entirely (no terminal line, log file line, event or metric);
+/// `Some(text)` replaces the message, so the terminal, the log file and event
+/// subscribers all see the rewritten text. Use it for redacting secrets or
+/// enriching messages. It applies to the app logging methods and [`format`],
+/// after the [`min_level`] check, and to command output built with the
+/// `get_*!` macros, where a dropped message yields an empty string.
+///
+/// Logging from inside the filter is allowed and bypasses it, so the filter
+/// cannot recurse. It must not call `set_log_filter` or [`clear_log_filter`]
+/// itself.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use daemon_console::{TerminalApp, logger};
+///
+/// logger::set_log_filter(|_level, message, _module| {
+///     if message.contains("heartbeat") {
+///         None
+///     } else {
+///         Some(message.replace("hunter2", "[redacted]"))
+///     }
+/// });
+///
+/// let mut app = TerminalApp::new();
+/// let mut events = app.subscribe_events().unwrap();
+/// assert_eq!(app.capture_output(|app| app.info("heartbeat ok")), "");
+/// assert!(events.try_recv().is_err());
+///
+/// let text = app.capture_output(|app| app.info("login with password hunter2"));
+/// assert!(text.contains("password [redacted]"));
+///
+/// let response = daemon_console::get_info!("token hunter2", "CommandResp");
+/// assert!(response.contains("token [redacted]"));
+///
+/// logger::clear_log_filter();
+/// ```
+pub fn set_log_filter<F>(filter: F)
+where
+    F: Fn(LogLevel, &str, Option<&str>) -> Option<String> + Send + Sync + 'static,
+{
+    *LOG_FILTER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(filter));
+}
+
+/// Removes the log filter.
+pub fn clear_log_filter() {
+    *LOG_FILTER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Resets [`IN_FILTER`] even if the filter panics.
+struct FilterGuard;
+
+impl Drop for FilterGuard {
+    fn drop(&mut self) {
+        IN_FILTER.set(false);
+    }
+}
+
+/// Runs the log filter on a message, returning `None` if it drops the message.
+///
+/// The message is returned unchanged when no filter is set, or when called from
+/// within the filter.
+pub fn filter_message<'a>(
+    level: LogLevel,
+    message: &'a str,
+    module_name: Option<&str>,
+) -> Option<Cow<'a, str>> {
+    if IN_FILTER.get() {
+        return Some(Cow::Borrowed(message));
+    }
+    let filter = LOG_FILTER.read().unwrap_or_else(|e| e.into_inner());
+    let Some(filter) = filter.as_ref() else {
+        return Some(Cow::Borrowed(message));
+    };
+    IN_FILTER.set(true);
+    let _guard = FilterGuard;
+    filter(level, message, module_name).map(Cow::Owned)
+}
+
 /// Formats a log message with timestamp, level indicator, and color coding.
 ///
 /// # Arguments
@@ -37,23 +514,44 @@ pub enum LogLevel {
 /// println!("{}", msg);
 /// ```
 pub fn log_message(level: LogLevel, message: &str, module_name: Option<&str>) -> String {
-    let now = Local::now();
-    let timestamp = now.format("%H:%M:%S").to_string();
+    format_log_line(level, message, module_name, color_enabled())
+}
+
+/// Formats a log message like [`log_message`] but never adds ANSI styling.
+///
+/// Used for plain-text sinks such as log files, independently of the global
+/// color switch that applies to the terminal.
+pub fn log_message_plain(level: LogLevel, message: &str, module_name: Option<&str>) -> String {
+    format_log_line(level, message, module_name, false)
+}
 
-    let (level_str, color) = match level {
-        LogLevel::Info => ("INFO", Color::Green),
-        LogLevel::Warn => ("WARN", Color::Yellow),
-        LogLevel::Error => ("ERROR", Color::Red),
-        LogLevel::Debug => ("DEBUG", Color::DarkGrey),
-        LogLevel::Critical => ("CRITICAL", Color::AnsiValue(5)),
+fn format_log_line(
+    level: LogLevel,
+    message: &str,
+    module_name: Option<&str>,
+    colored: bool,
+) -> String {
+    let timestamp = if show_timestamp() {
+        now().format("[%H:%M:%S] ").to_string()
+    } else {
+        String::new()
     };
 
-    let module_prefix = module_name.map_or_else(String::new, |name| format!("{}/", name));
+    let level_str = level.as_str();
+    let color = theme().color(level);
+
+    let module_prefix = module_name.map_or_else(String::new, |name| {
+        format!("{}{}", name, module_separator())
+    });
+
+    if !colored {
+        return format!("{}[{}{}] {}", timestamp, module_prefix, level_str, message);
+    }
 
     match level {
         LogLevel::Info | LogLevel::Warn | LogLevel::Error | LogLevel::Critical => {
             format!(
-                "[{}] {}[{}{}{}{}{}]{} {}{}",
+                "{}{}[{}{}{}{}{}]{} {}{}",
                 timestamp,
                 style::Attribute::Bold,
                 module_prefix,
@@ -68,7 +566,7 @@ pub fn log_message(level: LogLevel, message: &str, module_name: Option<&str>) ->
         }
         LogLevel::Debug => {
             format!(
-                "{}{}[{}] [{}{}] {}{}{}",
+                "{}{}{}[{}{}] {}{}{}",
                 SetForegroundColor(color),
                 style::Attribute::Italic,
                 timestamp,
@@ -84,11 +582,26 @@ pub fn log_message(level: LogLevel, message: &str, module_name: Option<&str>) ->
 
 /// Format multi-line messages with log-levels.
 ///
+/// The message goes through the [log filter](set_log_filter) first; an empty
+/// string is returned when the filter drops it.
+///
 /// > Middleware method for macros like `get_info!`.
 pub fn format_multiline_message(
     level: LogLevel,
     message: &str,
     module_name: Option<&str>,
+) -> String {
+    match filter_message(level, message, module_name) {
+        Some(message) => format_filtered_message(level, &message, module_name),
+        None => String::new(),
+    }
+}
+
+/// Formats a message that already went through the log filter.
+pub(crate) fn format_filtered_message(
+    level: LogLevel,
+    message: &str,
+    module_name: Option<&str>,
 ) -> String {
     if !message.contains('\n') {
         return log_message(level, message, module_name);
@@ -101,6 +614,42 @@ pub fn format_multiline_message(
         .join("\n")
 }
 
+/// Formats a log message with the global configuration applied.
+///
+/// Returns `None` when `level` is below the [`min_level`] or the
+/// [log filter](set_log_filter) drops the message; otherwise the formatted
+/// (possibly multi-line) entry, colored unless colors are disabled. Works without
+/// a `TerminalApp`.
+///
+/// # Examples
+///
+/// ```rust,standalone_crate
+/// use daemon_console::logger::{self, LogLevel};
+///
+/// logger::set_color_enabled(false);
+/// logger::set_min_level(LogLevel::Warn);
+///
+/// assert!(logger::format(LogLevel::Info, "chatty", None).is_none());
+/// let line = logger::format(LogLevel::Warn, "disk almost full", Some("fs")).unwrap();
+/// assert!(line.ends_with("[fs/WARN] disk almost full"));
+/// ```
+pub fn format(level: LogLevel, message: &str, module_name: Option<&str>) -> Option<String> {
+    if !level_enabled(level) {
+        return None;
+    }
+    let message = filter_message(level, message, module_name)?;
+    Some(format_filtered_message(level, &message, module_name))
+}
+
+/// Plain-text counterpart of [`format_multiline_message`], without ANSI styling.
+pub fn format_multiline_plain(level: LogLevel, message: &str, module_name: Option<&str>) -> String {
+    message
+        .lines()
+        .map(|line| log_message_plain(level, line, module_name))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
 /// Macro for creating info-level log messages.
 ///
 /// # Examples
@@ -5,8 +5,9 @@
 
 use async_trait::async_trait;
 use crossterm::terminal::disable_raw_mode;
-use daemon_console::{AsyncCommandHandler, TerminalApp, get_debug, get_error, get_info, get_warn};
-use std::io::{Write, stdout};
+use daemon_console::{
+    AsyncCommandHandler, TerminalApp, args::Args, get_debug, get_error, get_info, get_warn,
+};
 use std::process::Command;
 use std::time::Duration;
 use tokio::time::sleep;
@@ -33,6 +34,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    // One-shot mode: `daemon_console --exec "<command>"` runs a single command and exits.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--exec") {
+        let line = args.get(pos + 1).ok_or("--exec requires a command")?;
+        app.run_once(line).await?;
+        return Ok(());
+    }
+
     let startup_message = get_info!(
         "Running in async mode (v0.3.0+). Press Ctrl+D or Ctrl+C twice to exit.",
         "Daemon Console"
@@ -54,7 +63,7 @@ impl AsyncCommandHandler for SleepCommand {
             return get_info!("Usage: wait <seconds>", "CommandHelp");
         }
 
-        match args[0].parse::<u64>() {
+        match Args::new(args).get::<u64>(0) {
             Ok(seconds) => {
                 sleep(Duration::from_secs(seconds)).await;
                 app.info("Wake up!");
@@ -63,12 +72,7 @@ impl AsyncCommandHandler for SleepCommand {
                     "CommandResp"
                 )
             }
-            Err(_) => {
-                get_error!(
-                    "Invalid number format. Please provide a valid number of seconds.",
-                    "CommandResp"
-                )
-            }
+            Err(e) => get_error!(&e.to_string(), "CommandResp"),
         }
     }
 
@@ -95,21 +99,13 @@ async fn register_commands(app: &mut TerminalApp) {
                     .output()
                     .map_or_else(
                         |e| get_error!(&format!("Error executing command: {}", e), "CommandResp"),
-                        |output| {
-                            String::from_utf8_lossy(&output.stdout)
-                                .trim_end()
-                                .to_string()
-                        },
+                        |output| String::from_utf8_lossy(&output.stdout).to_string(),
                     )
             } else {
                 app.info("Seems system is Unix-like, using 'ls' command.");
                 Command::new("ls").args(args).output().map_or_else(
                     |e| get_error!(&format!("Error executing command: {}", e), "CommandResp"),
-                    |output| {
-                        String::from_utf8_lossy(&output.stdout)
-                            .trim_end()
-                            .to_string()
-                    },
+                    |output| String::from_utf8_lossy(&output.stdout).to_string(),
                 )
             }
         }),
@@ -169,11 +165,10 @@ async fn register_commands(app: &mut TerminalApp) {
                 .count();
 
             if crash_count > 1 || args.contains(&"--confirm") {
-                let mut stdout = stdout();
                 app.warn("You have confirmed to crash the application.");
                 app.critical("Crashing...");
                 disable_raw_mode().expect("");
-                stdout.flush().expect("");
+                app.flush().expect("");
                 panic!("Application crashed intentionally!");
             }
 
@@ -0,0 +1,121 @@
+//! Output targets for the console's rendering.
+
+use std::io::{self, Stdout, Write, stdout};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Where `TerminalApp` writes its log lines, echo and input line.
+pub enum ConsoleOutput {
+    /// The process's standard output (default).
+    Stdout(Stdout),
+    /// Discards everything; cursor and input-line rendering is skipped entirely.
+    ///
+    /// Meant as a testing and benchmarking aid: commands, logging calls and events
+    /// still run, but no terminal rendering cost is paid.
+    Sink,
+    /// An in-memory buffer that can be read back through a clone of it.
+    Buffer(OutputBuffer),
+    /// Any other writer, e.g. a file or a socket.
+    Writer(Box<dyn Write + Send>),
+}
+
+impl ConsoleOutput {
+    /// Returns an output writing to standard output.
+    pub fn stdout() -> Self {
+        ConsoleOutput::Stdout(stdout())
+    }
+
+    /// Returns whether this output discards everything written to it.
+    pub fn is_sink(&self) -> bool {
+        matches!(self, ConsoleOutput::Sink)
+    }
+}
+
+impl Default for ConsoleOutput {
+    fn default() -> Self {
+        Self::stdout()
+    }
+}
+
+impl Write for ConsoleOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ConsoleOutput::Stdout(out) => out.write(buf),
+            ConsoleOutput::Sink => Ok(buf.len()),
+            ConsoleOutput::Buffer(out) => out.write(buf),
+            ConsoleOutput::Writer(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ConsoleOutput::Stdout(out) => out.flush(),
+            ConsoleOutput::Sink => Ok(()),
+            ConsoleOutput::Buffer(out) => out.flush(),
+            ConsoleOutput::Writer(out) => out.flush(),
+        }
+    }
+}
+
+/// Shared in-memory output, see [`ConsoleOutput::Buffer`].
+///
+/// Clones share the same bytes, so a clone kept by the caller reads what the
+/// console wrote, including cursor movement and other escape sequences.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+///
+/// let output = OutputBuffer::new();
+/// let mut app = TerminalApp::new();
+/// app.set_output(ConsoleOutput::Buffer(output.clone()));
+///
+/// app.info("first");
+/// assert!(output.take().contains("first"));
+/// assert!(output.is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    /// Creates an empty buffer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns everything written so far, decoding invalid UTF-8 lossily.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.bytes()).into_owned()
+    }
+
+    /// Returns everything written so far and empties the buffer.
+    pub fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.bytes());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    /// Empties the buffer.
+    pub fn clear(&self) {
+        self.bytes().clear();
+    }
+
+    /// Returns whether nothing was written since the buffer was last emptied.
+    pub fn is_empty(&self) -> bool {
+        self.bytes().is_empty()
+    }
+
+    fn bytes(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! Paging long command output.
+//!
+//! When paging applies to a command, output taller than a page is printed one
+//! page at a time. The rest is held back behind a `-- More --` prompt that owns
+//! the keyboard like other modal prompts: Space shows the next page, Enter or
+//! Down the next line, and `q`, Esc or Ctrl+C discards what is left.
+//!
+//! Paging is off by default. [`TerminalApp::set_pager`] turns it on for all
+//! commands, and [`ConsoleCore::set_command_paging`] overrides that for single
+//! commands.
+
+use crate::prompt::ModalPrompt;
+use crate::{ConsoleCore, TerminalApp};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::VecDeque;
+
+/// Page height used when the terminal size is unknown.
+const FALLBACK_PAGE_HEIGHT: usize = 24;
+
+impl ConsoleCore {
+    /// Overrides the global pager setting for one command.
+    ///
+    /// `Some(true)` always pages the command's long output, e.g. for `help` or a
+    /// `log` command, even when [`set_pager`](TerminalApp::set_pager) is off;
+    /// `Some(false)` never pages it, even when the pager is on; `None` removes the
+    /// override. The per-command setting always wins over the global one. The
+    /// command is identified by the first word of the submitted line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, OutputBuffer, TerminalApp};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let captured = OutputBuffer::new();
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Buffer(captured.clone()));
+    ///     app.register_command(
+    ///         "log",
+    ///         Box::new(|_: &mut TerminalApp, _: &[&str]| {
+    ///             (1..=10).map(|i| format!("entry {}\n", i)).collect()
+    ///         }),
+    ///     );
+    ///     app.set_command_paging("log", Some(true));
+    ///     app.set_page_height(Some(4));
+    ///
+    ///     app.current_input = "log".to_string();
+    ///     app.handle_enter_key("> ").await.unwrap();
+    ///     let text = captured.contents();
+    ///     assert!(text.contains("entry 3\n"));
+    ///     assert!(!text.contains("entry 4\n"));
+    ///     assert!(app.is_modal_active());
+    ///
+    ///     let quit = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE);
+    ///     app.process_event(Event::Key(quit)).await.unwrap();
+    ///     assert!(!app.is_modal_active());
+    /// }
+    /// ```
+    pub fn set_command_paging(&mut self, name: &str, paging: Option<bool>) {
+        match paging {
+            Some(paging) => {
+                self.command_paging.insert(name.to_string(), paging);
+            }
+            None => {
+                self.command_paging.remove(name);
+            }
+        }
+    }
+}
+
+impl TerminalApp {
+    /// Pages long output of every command without a per-command override.
+    ///
+    /// Off by default. Settings made with
+    /// [`set_command_paging`](ConsoleCore::set_command_paging) take precedence.
+    pub fn set_pager(&mut self, enabled: bool) {
+        self.pager = enabled;
+    }
+
+    /// Sets the number of rows per page, including the `-- More --` prompt row.
+    ///
+    /// `None` (the default) uses the terminal height.
+    pub fn set_page_height(&mut self, rows: Option<usize>) {
+        self.page_height = rows;
+    }
+
+    /// Returns whether the output of `command_line` is paged.
+    fn pages_command(&self, command_line: &str) -> bool {
+        let name = command_line.split_whitespace().next().unwrap_or_default();
+        let paging = self.command_paging.get(name).copied().unwrap_or(self.pager);
+        paging && !self.output_discarded() && self.capture.is_none()
+    }
+
+    /// Rows per page, including the prompt row.
+    fn page_rows(&self) -> usize {
+        self.page_height
+            .or_else(|| {
+                crossterm::terminal::size()
+                    .ok()
+                    .map(|(_, rows)| rows as usize)
+            })
+            .unwrap_or(FALLBACK_PAGE_HEIGHT)
+            .max(2)
+    }
+
+    /// Prints a command's output, paging it if paging applies to the command.
+    pub(crate) fn render_paged_output(&mut self, command_line: &str, output: &str) {
+        if !self.pages_command(command_line) {
+            self.render_command_output(output);
+            return;
+        }
+        let mut lines: VecDeque<String> = output.trim_end().lines().map(str::to_string).collect();
+        if let Some(ModalPrompt::Pager { remaining }) = &mut self.modal {
+            remaining.extend(lines);
+            let _ = self.render_input_line();
+            return;
+        }
+        if lines.len() < self.page_rows() {
+            self.render_command_output(output);
+            return;
+        }
+        self.print_page_lines(&mut lines, self.page_rows() - 1);
+        self.modal = Some(ModalPrompt::Pager { remaining: lines });
+        let _ = self.render_input_line();
+    }
+
+    /// Prints up to `count` held-back lines.
+    fn print_page_lines(&mut self, lines: &mut VecDeque<String>, count: usize) {
+        let shown = count.min(lines.len());
+        for line in lines.drain(..shown) {
+            self.print_output_line(&line);
+        }
+    }
+
+    /// Handles a key while the pager prompt is active.
+    pub(crate) fn handle_pager_key(&mut self, key: KeyEvent) {
+        let Some(ModalPrompt::Pager { remaining }) = &mut self.modal else {
+            return;
+        };
+        let mut lines = std::mem::take(remaining);
+        let is_ctrl_c = key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL;
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q' | 'Q') => lines.clear(),
+            _ if is_ctrl_c => lines.clear(),
+            KeyCode::Char(' ') | KeyCode::PageDown => {
+                self.print_page_lines(&mut lines, self.page_rows() - 1)
+            }
+            KeyCode::Enter | KeyCode::Down => self.print_page_lines(&mut lines, 1),
+            _ => {}
+        }
+        self.modal = if lines.is_empty() {
+            None
+        } else {
+            Some(ModalPrompt::Pager { remaining: lines })
+        };
+    }
+}
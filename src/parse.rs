@@ -0,0 +1,292 @@
+//! Quote-aware parsing of command lines.
+//!
+//! Words are separated by whitespace. Single quotes keep their content literally,
+//! double quotes allow `\"` and `\\` escapes, and outside quotes a backslash escapes
+//! the next character. Operators such as `>` are only recognized outside quotes and
+//! outside `$(...)` substitutions.
+
+use crate::utils::strip_ansi;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+/// A command line split into the command name and its arguments.
+///
+/// Produced once by the dispatcher, so handlers and hooks can read it instead of
+/// parsing the line again; see [`ConsoleCore::current_command`](crate::ConsoleCore::current_command).
+/// [`arg_refs`](Self::arg_refs) adapts it to the `&[&str]` handler signature.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::parse::ParsedCommand;
+///
+/// let parsed = ParsedCommand::parse(r#"  deploy   "my app"  --env 'prod eu'  "#).unwrap();
+/// assert_eq!(parsed.name, "deploy");
+/// assert_eq!(parsed.args, ["my app", "--env", "prod eu"]);
+/// assert_eq!(parsed.raw, r#"  deploy   "my app"  --env 'prod eu'  "#);
+/// assert_eq!(parsed.arg_refs(), ["my app", "--env", "prod eu"]);
+/// assert!(ParsedCommand::parse("   ").is_none());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedCommand {
+    /// The first word, with quotes and escapes removed.
+    pub name: String,
+    /// The remaining words, with quotes and escapes removed.
+    pub args: Vec<String>,
+    /// The line as it was given to the dispatcher.
+    pub raw: String,
+}
+
+impl ParsedCommand {
+    /// Tokenizes a command line, returning `None` if it contains no words.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = tokenize(line).into_iter();
+        let name = words.next()?;
+        Some(Self {
+            name,
+            args: words.collect(),
+            raw: line.to_string(),
+        })
+    }
+
+    /// Returns the arguments as string slices, as passed to command handlers.
+    pub fn arg_refs(&self) -> Vec<&str> {
+        self.args.iter().map(String::as_str).collect()
+    }
+}
+
+/// Splits a command line into words, removing quotes and escapes.
+///
+/// An unterminated quote extends to the end of the line.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::parse::tokenize;
+///
+/// assert_eq!(tokenize(r#"say "hello world" 'a b' c\ d"#), ["say", "hello world", "a b", "c d"]);
+/// assert_eq!(tokenize("  spaced   out  "), ["spaced", "out"]);
+/// assert_eq!(tokenize(r#"quote "she said \"hi\"""#), ["quote", r#"she said "hi""#]);
+/// ```
+pub fn tokenize(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    current.push(c);
+                }
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(next @ ('"' | '\\')) => current.push(next),
+                            Some(next) => {
+                                current.push('\\');
+                                current.push(next);
+                            }
+                            None => current.push('\\'),
+                        },
+                        _ => current.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                current.push(chars.next().unwrap_or('\\'));
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            _ => {
+                in_word = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+/// Returns the byte offsets of characters that sit outside quotes, escapes and
+/// `$(...)` substitutions.
+pub(crate) fn unquoted_offsets(line: &str) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut substitution_depth = 0usize;
+    let mut prev = '\0';
+
+    for (idx, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            } else if c == '\\' && q == '"' {
+                escaped = true;
+            }
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '\'' || c == '"' {
+            quote = Some(c);
+        } else if c == '(' && prev == '$' {
+            substitution_depth += 1;
+        } else if c == ')' && substitution_depth > 0 {
+            substitution_depth -= 1;
+        } else if substitution_depth == 0 {
+            offsets.push(idx);
+        }
+        prev = c;
+    }
+    offsets
+}
+
+/// Output redirection parsed from the end of a command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirection {
+    /// File the output is written to.
+    pub path: String,
+    /// Whether to append (`>>`) instead of truncating (`>`).
+    pub append: bool,
+}
+
+impl Redirection {
+    /// Writes command output to the target file without ANSI styling.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or written.
+    pub fn write(&self, output: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(self.append)
+            .truncate(!self.append)
+            .open(&self.path)?;
+        for line in output.lines() {
+            writeln!(file, "{}", strip_ansi(line))?;
+        }
+        file.flush()
+    }
+}
+
+/// Splits a trailing `> file` or `>> file` redirection off a command line.
+///
+/// Returns the command part and the redirection, if any. A `>` inside quotes or
+/// a `$(...)` substitution is not treated as a redirection.
+///
+/// # Errors
+///
+/// Returns a message when the redirection has no target or more than one.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::parse::{Redirection, split_redirection};
+///
+/// let (cmd, redirect) = split_redirection("list >> 'out file.txt'").unwrap();
+/// assert_eq!(cmd, "list ");
+/// assert_eq!(redirect, Some(Redirection { path: "out file.txt".into(), append: true }));
+///
+/// assert_eq!(split_redirection("echo '1 > 0'").unwrap(), ("echo '1 > 0'", None));
+/// assert!(split_redirection("list >").is_err());
+/// ```
+pub fn split_redirection(line: &str) -> Result<(&str, Option<Redirection>), String> {
+    let Some(idx) = unquoted_offsets(line)
+        .into_iter()
+        .find(|&idx| line[idx..].starts_with('>'))
+    else {
+        return Ok((line, None));
+    };
+
+    let append = line[idx + 1..].starts_with('>');
+    let target_start = if append { idx + 2 } else { idx + 1 };
+    let mut target = tokenize(&line[target_start..]);
+    if target.len() != 1 {
+        return Err("Redirection needs exactly one target file".to_string());
+    }
+    Ok((
+        &line[..idx],
+        Some(Redirection {
+            path: target.remove(0),
+            append,
+        }),
+    ))
+}
+
+/// How a command in a chain depends on the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainLink {
+    /// Runs regardless of the previous result (first command, or after `;`).
+    Always,
+    /// Runs only if the previous command succeeded (after `&&`).
+    OnSuccess,
+}
+
+/// Splits a command line on unquoted `&&` and `;` separators.
+///
+/// Empty commands (e.g. from a trailing `;`) are dropped. Separators inside quotes
+/// or `$(...)` substitutions are kept as part of the command.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::parse::{ChainLink, split_chain};
+///
+/// assert_eq!(
+///     split_chain("build && deploy ; echo 'a;b && c'"),
+///     [
+///         (ChainLink::Always, "build"),
+///         (ChainLink::OnSuccess, "deploy"),
+///         (ChainLink::Always, "echo 'a;b && c'"),
+///     ]
+/// );
+/// ```
+pub fn split_chain(line: &str) -> Vec<(ChainLink, &str)> {
+    let mut commands = Vec::new();
+    let mut link = ChainLink::Always;
+    let mut start = 0;
+    let offsets = unquoted_offsets(line);
+    let mut iter = offsets.iter().copied().peekable();
+
+    while let Some(idx) = iter.next() {
+        let (next_link, len) = if line[idx..].starts_with("&&") && iter.peek() == Some(&(idx + 1)) {
+            (ChainLink::OnSuccess, 2)
+        } else if line[idx..].starts_with(';') {
+            (ChainLink::Always, 1)
+        } else {
+            continue;
+        };
+        let command = line[start..idx].trim();
+        if !command.is_empty() {
+            commands.push((link, command));
+        }
+        link = next_link;
+        start = idx + len;
+        if len == 2 {
+            iter.next();
+        }
+    }
+    let command = line[start..].trim();
+    if !command.is_empty() {
+        commands.push((link, command));
+    }
+    commands
+}
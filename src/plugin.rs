@@ -0,0 +1,73 @@
+//! Registering commands from plugins and background tasks.
+//!
+//! Commands can be added to a running console in two ways:
+//!
+//! - From any task, send [`AppAction::RegisterCommand`] or
+//!   [`AppAction::RegisterAsyncCommand`] through a [`ConsoleHandle`]; the console
+//!   registers the handler on its next loop iteration.
+//! - Bundle related commands in a [`CommandPlugin`] and pass it to
+//!   [`ConsoleCore::load_plugin`].
+//!
+//! # Dynamic libraries
+//!
+//! Handlers are Rust trait objects, which have no stable ABI. A plugin loaded from a
+//! shared library (e.g. with `libloading`) therefore must be built with the same
+//! compiler version and the same `daemon_console` version as the host. Within that
+//! constraint, export a constructor and let the host call it:
+//!
+//! ```ignore
+//! // In the plugin crate (crate-type = ["cdylib"] or ["dylib"])
+//! #[unsafe(no_mangle)]
+//! pub fn daemon_console_plugin() -> Box<dyn CommandPlugin> {
+//!     Box::new(MyPlugin)
+//! }
+//! ```
+//!
+//! The host keeps the library loaded for as long as any of its handlers may run,
+//! since unloading it would leave dangling vtables behind.
+//!
+//! [`AppAction::RegisterCommand`]: crate::AppAction::RegisterCommand
+//! [`AppAction::RegisterAsyncCommand`]: crate::AppAction::RegisterAsyncCommand
+//! [`ConsoleHandle`]: crate::ConsoleHandle
+
+use crate::ConsoleCore;
+
+/// A bundle of commands that registers itself with a console.
+pub trait CommandPlugin: Send + Sync {
+    /// Name used in diagnostics, e.g. `"metrics"`.
+    fn name(&self) -> &str;
+
+    /// Registers the plugin's commands.
+    fn register(&self, core: &mut ConsoleCore);
+}
+
+impl ConsoleCore {
+    /// Registers every command of a plugin.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleCore, TerminalApp, plugin::CommandPlugin};
+    ///
+    /// struct Greeter;
+    ///
+    /// impl CommandPlugin for Greeter {
+    ///     fn name(&self) -> &str {
+    ///         "greeter"
+    ///     }
+    ///
+    ///     fn register(&self, core: &mut ConsoleCore) {
+    ///         core.register_handler("greet", |_app: &mut TerminalApp, _args: &[&str]| {
+    ///             "Hello!".to_string()
+    ///         });
+    ///     }
+    /// }
+    ///
+    /// let mut core = ConsoleCore::new();
+    /// core.load_plugin(&Greeter);
+    /// assert!(core.has_command("greet"));
+    /// ```
+    pub fn load_plugin(&mut self, plugin: &dyn CommandPlugin) {
+        plugin.register(self);
+    }
+}
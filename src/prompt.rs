@@ -0,0 +1,258 @@
+//! Modal prompts that temporarily take over the input line.
+//!
+//! While a confirmation or hidden-input prompt is active, key events are routed to
+//! the prompt instead of the command line. Ctrl+C cancels the prompt rather than
+//! clearing input or starting the double-press exit countdown.
+//!
+//! For a temporary change of the regular prompt, see [`PromptGuard`].
+
+use crate::TerminalApp;
+use crossterm::event::{self, KeyCode, KeyEvent, KeyModifiers, poll};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+/// Restores the previous prompt when dropped, see [`TerminalApp::prompt_guard`].
+///
+/// The guard dereferences to the app, so it can be used in its place while the
+/// temporary prompt is shown.
+pub struct PromptGuard<'a> {
+    app: &'a mut TerminalApp,
+    previous: String,
+}
+
+impl Deref for PromptGuard<'_> {
+    type Target = TerminalApp;
+
+    fn deref(&self) -> &TerminalApp {
+        self.app
+    }
+}
+
+impl DerefMut for PromptGuard<'_> {
+    fn deref_mut(&mut self) -> &mut TerminalApp {
+        self.app
+    }
+}
+
+impl Drop for PromptGuard<'_> {
+    fn drop(&mut self) {
+        self.app.prompt = std::mem::take(&mut self.previous);
+        let _ = self.app.render_input_line();
+    }
+}
+
+/// Result of a modal prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptOutcome {
+    /// The user answered a confirmation with yes (`true`) or no (`false`).
+    Confirmed(bool),
+    /// The user submitted hidden input with Enter.
+    Input(String),
+    /// The user pressed Ctrl+C or Esc.
+    Cancelled,
+}
+
+/// An active modal prompt.
+pub(crate) enum ModalPrompt {
+    Confirm {
+        question: String,
+    },
+    Hidden {
+        prompt: String,
+        buffer: String,
+    },
+    /// Output held back by the pager, see [`crate::pager`].
+    Pager {
+        remaining: VecDeque<String>,
+    },
+}
+
+impl ModalPrompt {
+    /// Text shown in place of the command prompt.
+    pub(crate) fn prompt_text(&self) -> String {
+        match self {
+            ModalPrompt::Confirm { question } => format!("{} [y/n] ", question),
+            ModalPrompt::Hidden { prompt, .. } => prompt.clone(),
+            ModalPrompt::Pager { remaining } => format!(
+                "-- More ({} lines left, Space/Enter/q) -- ",
+                remaining.len()
+            ),
+        }
+    }
+}
+
+impl TerminalApp {
+    /// Shows `prompt` until the returned guard is dropped, then restores the previous one.
+    ///
+    /// Both the change and the restore redraw the input line. Because the restore
+    /// happens on drop, an early return or `?` in a command cannot leave the
+    /// temporary prompt behind.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    ///
+    /// let mut app = TerminalApp::new();
+    /// app.set_output(ConsoleOutput::Sink);
+    /// {
+    ///     let mut guard = app.prompt_guard("db> ");
+    ///     assert_eq!(guard.prompt(), "db> ");
+    ///     guard.info("Entered database mode");
+    /// }
+    /// assert_eq!(app.prompt(), "> ");
+    /// ```
+    pub fn prompt_guard(&mut self, prompt: &str) -> PromptGuard<'_> {
+        let previous = std::mem::replace(&mut self.prompt, prompt.to_string());
+        let _ = self.render_input_line();
+        PromptGuard {
+            app: self,
+            previous,
+        }
+    }
+
+    /// Starts a yes/no confirmation prompt without waiting for the answer.
+    ///
+    /// The answer is delivered through [`take_prompt_outcome`](Self::take_prompt_outcome)
+    /// once `process_event` has seen `y`, `n`, Esc or Ctrl+C.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    /// use daemon_console::{ConsoleOutput, TerminalApp, prompt::PromptOutcome};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     app.current_input = "draft".to_string();
+    ///
+    ///     app.begin_confirm("Delete everything?");
+    ///     let ctrl_c = Event::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+    ///     let quit = app.process_event(ctrl_c).await.unwrap();
+    ///
+    ///     assert!(!quit);
+    ///     assert_eq!(app.take_prompt_outcome(), Some(PromptOutcome::Cancelled));
+    ///     assert_eq!(app.current_input, "draft");
+    /// }
+    /// ```
+    pub fn begin_confirm(&mut self, question: &str) {
+        self.modal = Some(ModalPrompt::Confirm {
+            question: question.to_string(),
+        });
+        self.prompt_outcome = None;
+        let _ = self.render_input_line();
+    }
+
+    /// Starts a hidden-input prompt (e.g. for a password) without waiting for it.
+    ///
+    /// Typed characters are not echoed, and the
+    /// [key observer](Self::set_key_observer) does not see them. Enter submits,
+    /// Ctrl+C or Esc cancels.
+    pub fn begin_hidden_input(&mut self, prompt: &str) {
+        self.modal = Some(ModalPrompt::Hidden {
+            prompt: prompt.to_string(),
+            buffer: String::new(),
+        });
+        self.prompt_outcome = None;
+        let _ = self.render_input_line();
+    }
+
+    /// Returns whether a modal prompt currently owns the input line.
+    pub fn is_modal_active(&self) -> bool {
+        self.modal.is_some()
+    }
+
+    /// Takes the outcome of the last finished modal prompt, if any.
+    pub fn take_prompt_outcome(&mut self) -> Option<PromptOutcome> {
+        self.prompt_outcome.take()
+    }
+
+    /// Asks a yes/no question and waits for the answer.
+    ///
+    /// Reads terminal events itself, so call it from the task that owns the app
+    /// and not while `run` is polling events.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading terminal events fails.
+    pub async fn confirm(
+        &mut self,
+        question: &str,
+    ) -> Result<PromptOutcome, Box<dyn std::error::Error>> {
+        self.begin_confirm(question);
+        self.wait_for_prompt().await
+    }
+
+    /// Reads a line without echoing it and waits for submission.
+    ///
+    /// Same event-reading caveat as [`confirm`](Self::confirm).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading terminal events fails.
+    pub async fn read_hidden(
+        &mut self,
+        prompt: &str,
+    ) -> Result<PromptOutcome, Box<dyn std::error::Error>> {
+        self.begin_hidden_input(prompt);
+        self.wait_for_prompt().await
+    }
+
+    /// Feeds terminal events to the active prompt until it finishes.
+    async fn wait_for_prompt(&mut self) -> Result<PromptOutcome, Box<dyn std::error::Error>> {
+        loop {
+            if let Some(outcome) = self.take_prompt_outcome() {
+                return Ok(outcome);
+            }
+            self.stdout_handle.flush()?;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if poll(Duration::from_millis(0))? {
+                let event = event::read()?;
+                self.process_event(event).await?;
+            }
+        }
+    }
+
+    /// Handles a key while a modal prompt is active.
+    pub(crate) fn handle_modal_key(
+        &mut self,
+        key: KeyEvent,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(ModalPrompt::Pager { .. }) = self.modal {
+            self.handle_pager_key(key);
+            return self.render_input_line();
+        }
+        let is_ctrl_c = key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL;
+        let outcome = match (&mut self.modal, key.code) {
+            (_, KeyCode::Esc) => Some(PromptOutcome::Cancelled),
+            (_, _) if is_ctrl_c => Some(PromptOutcome::Cancelled),
+            (Some(ModalPrompt::Confirm { .. }), KeyCode::Char('y' | 'Y')) => {
+                Some(PromptOutcome::Confirmed(true))
+            }
+            (Some(ModalPrompt::Confirm { .. }), KeyCode::Char('n' | 'N')) => {
+                Some(PromptOutcome::Confirmed(false))
+            }
+            (Some(ModalPrompt::Hidden { buffer, .. }), KeyCode::Enter) => {
+                Some(PromptOutcome::Input(std::mem::take(buffer)))
+            }
+            (Some(ModalPrompt::Hidden { buffer, .. }), KeyCode::Backspace) => {
+                buffer.pop();
+                None
+            }
+            (Some(ModalPrompt::Hidden { buffer, .. }), KeyCode::Char(c)) => {
+                buffer.push(c);
+                None
+            }
+            _ => None,
+        };
+        if let Some(outcome) = outcome {
+            self.modal = None;
+            self.prompt_outcome = Some(outcome);
+        }
+        self.render_input_line()
+    }
+}
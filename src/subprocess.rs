@@ -0,0 +1,190 @@
+//! Running child processes with their output streamed into the console.
+//!
+//! [`ConsoleCore::spawn_subprocess`] starts a process and tracks it like a
+//! background task. Each output line is printed as a log entry under the given
+//! name and reported as a [`SubprocessLog`] event; the process's start and exit
+//! are reported as [`SubprocessStarted`] and [`SubprocessExited`].
+//!
+//! [`SubprocessLog`]: crate::events::DaemonConsoleEvent::SubprocessLog
+//! [`SubprocessStarted`]: crate::events::DaemonConsoleEvent::SubprocessStarted
+//! [`SubprocessExited`]: crate::events::DaemonConsoleEvent::SubprocessExited
+
+use crate::events::DaemonConsoleEvent;
+use crate::logger::LogLevel;
+use crate::{AppAction, ConsoleCore, ConsoleHandle};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+
+/// Reports the exit of a child exactly once, even if its task is aborted.
+struct ExitReporter {
+    pid: u32,
+    handle: Option<ConsoleHandle>,
+}
+
+impl ExitReporter {
+    /// Sends the exit event, waiting for queue capacity in bounded mode.
+    async fn report(mut self, status: Option<i32>) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.send(exit_event(self.pid, status)).await;
+        }
+    }
+}
+
+impl Drop for ExitReporter {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.try_send(exit_event(self.pid, None));
+        }
+    }
+}
+
+fn exit_event(pid: u32, status: Option<i32>) -> AppAction {
+    AppAction::Emit(DaemonConsoleEvent::SubprocessExited {
+        pid,
+        status,
+        timestamp: DaemonConsoleEvent::now_ts(),
+    })
+}
+
+/// Forwards each line of a child's output stream to the console.
+async fn forward_lines<R>(
+    stream: Option<R>,
+    pid: u32,
+    name: String,
+    level: LogLevel,
+    handle: ConsoleHandle,
+) where
+    R: AsyncRead + Unpin,
+{
+    let Some(stream) = stream else {
+        return;
+    };
+    let mut lines = BufReader::new(stream).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let _ = handle
+            .send(AppAction::Logger(
+                level,
+                line.clone(),
+                Some(name.clone()),
+                Some(false),
+            ))
+            .await;
+        let _ = handle
+            .send(AppAction::Emit(DaemonConsoleEvent::SubprocessLog {
+                pid,
+                message: line,
+                timestamp: DaemonConsoleEvent::now_ts(),
+            }))
+            .await;
+    }
+}
+
+impl ConsoleCore {
+    /// Starts a child process whose output is streamed into the console.
+    ///
+    /// Standard output lines are logged at info level and standard error lines at
+    /// warn level, both under `name`, and each line is also emitted as a
+    /// `SubprocessLog` event. A `SubprocessStarted` event is emitted before this
+    /// returns, and exactly one `SubprocessExited` event follows once the child
+    /// is gone. Its `status` is `None` when the exit code is unknown, e.g. when the
+    /// child was killed by a signal, could not be waited on, or was killed because
+    /// its task was aborted.
+    ///
+    /// The child is tracked like [`spawn_tracked`](Self::spawn_tracked) tasks under
+    /// `name`. Output and exit reports travel through the action channel, so they
+    /// are applied by the `run` loop or [`process_pending_actions`].
+    ///
+    /// [`process_pending_actions`]: crate::TerminalApp::process_pending_actions
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the process cannot be started; no events are emitted
+    /// then.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daemon_console::events::DaemonConsoleEvent;
+    /// use daemon_console::{ConsoleOutput, TerminalApp};
+    /// use tokio::process::Command;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     if cfg!(not(unix)) {
+    ///         return;
+    ///     }
+    ///     let mut app = TerminalApp::new();
+    ///     app.set_output(ConsoleOutput::Sink);
+    ///     let mut events = app.subscribe_events().unwrap();
+    ///
+    ///     let mut command = Command::new("sh");
+    ///     command.args(["-c", "echo hello; exit 3"]);
+    ///     let pid = app.spawn_subprocess("greeter", command).unwrap();
+    ///
+    ///     while !app.running_jobs().is_empty() {
+    ///         tokio::task::yield_now().await;
+    ///     }
+    ///     app.process_pending_actions();
+    ///     let mut seen = Vec::new();
+    ///     while let Ok(event) = events.try_recv() {
+    ///         seen.push(event);
+    ///     }
+    ///
+    ///     assert!(matches!(seen.first(), Some(DaemonConsoleEvent::SubprocessStarted { pid: p, .. }) if *p == pid));
+    ///     assert!(seen.iter().any(|e| matches!(e, DaemonConsoleEvent::SubprocessLog { message, .. } if message == "hello")));
+    ///     let exits: Vec<_> = seen
+    ///         .iter()
+    ///         .filter_map(|e| match e {
+    ///             DaemonConsoleEvent::SubprocessExited { status, .. } => Some(*status),
+    ///             _ => None,
+    ///         })
+    ///         .collect();
+    ///     assert_eq!(exits, [Some(3)]);
+    /// }
+    /// ```
+    pub fn spawn_subprocess(&mut self, name: &str, mut command: Command) -> std::io::Result<u32> {
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let mut child = command.spawn()?;
+        let pid = child.id().unwrap_or_default();
+        if self.events_wanted() {
+            self.emit_events(DaemonConsoleEvent::SubprocessStarted {
+                pid,
+                command: name.to_string(),
+                timestamp: DaemonConsoleEvent::now_ts(),
+            });
+        }
+
+        let handle = self.action_sender.clone();
+        let stdout = forward_lines(
+            child.stdout.take(),
+            pid,
+            name.to_string(),
+            LogLevel::Info,
+            handle.clone(),
+        );
+        let stderr = forward_lines(
+            child.stderr.take(),
+            pid,
+            name.to_string(),
+            LogLevel::Warn,
+            handle.clone(),
+        );
+        let reporter = ExitReporter {
+            pid,
+            handle: Some(handle),
+        };
+        self.spawn_tracked(name, async move {
+            // Drain output first so every line is reported before the exit
+            tokio::join!(stdout, stderr);
+            let status = child.wait().await.ok().and_then(|status| status.code());
+            reporter.report(status).await;
+            String::new()
+        });
+        Ok(pid)
+    }
+}
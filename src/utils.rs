@@ -1,6 +1,287 @@
+use crate::CarriageReturnMode;
 use chrono::{Local, TimeZone};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub fn get_local_timestring(time: i64) -> String {
     let datetime = Local.timestamp_millis_opt(time).unwrap();
     datetime.format("%H:%M:%S").to_string()
 }
+
+/// Removes ANSI escape sequences (CSI styling and OSC sequences) from a string.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::utils::strip_ansi;
+///
+/// assert_eq!(strip_ansi("\x1b[32mINFO\x1b[0m ready"), "INFO ready");
+/// assert_eq!(strip_ansi("\x1b]8;;https://example.com\x1b\\site\x1b]8;;\x1b\\"), "site");
+/// ```
+pub fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters end with a byte in '@'..='~'
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ST (ESC \)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// Returns the number of terminal columns a string occupies, ignoring ANSI escapes.
+pub fn display_width(text: &str) -> usize {
+    strip_ansi(text).width()
+}
+
+/// Cuts plain text to at most `max_width` columns, ending with `…` when shortened.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::utils::truncate_to_width;
+///
+/// assert_eq!(truncate_to_width("hello world", 8), "hello w…");
+/// assert_eq!(truncate_to_width("日本語テキスト", 7), "日本語…");
+/// assert_eq!(truncate_to_width("short", 10), "short");
+/// ```
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width + 1 > max_width {
+            break;
+        }
+        truncated.push(c);
+        width += char_width;
+    }
+    if max_width > 0 {
+        truncated.push('…');
+    }
+    truncated
+}
+
+/// Returns the Levenshtein distance between two strings, counted in chars.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::utils::edit_distance;
+///
+/// assert_eq!(edit_distance("lst", "list"), 1);
+/// assert_eq!(edit_distance("kitten", "sitting"), 3);
+/// assert_eq!(edit_distance("", "abc"), 3);
+/// ```
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Formats rows as a table with columns aligned by display width.
+///
+/// The header row is followed by a dashed separator. Rows may have fewer or more
+/// cells than there are headers; missing cells are left blank. Returns an empty
+/// string when there are neither headers nor rows. Cells should be plain text.
+///
+/// # Examples
+///
+/// ```
+/// use daemon_console::utils::format_table;
+///
+/// let table = format_table(&["name", "city"], &[vec!["Zoë", "東京"], vec!["Bob"]]);
+/// assert_eq!(table, "name  city\n----  ----\nZoë   東京\nBob");
+/// assert_eq!(format_table::<&str, &str, Vec<&str>>(&[], &[]), "");
+/// ```
+pub fn format_table<H, C, R>(headers: &[H], rows: &[R]) -> String
+where
+    H: AsRef<str>,
+    C: AsRef<str>,
+    R: AsRef<[C]>,
+{
+    let header: Vec<&str> = headers.iter().map(AsRef::as_ref).collect();
+    let body: Vec<Vec<&str>> = rows
+        .iter()
+        .map(|row| row.as_ref().iter().map(AsRef::as_ref).collect())
+        .collect();
+
+    let columns = body
+        .iter()
+        .map(Vec::len)
+        .chain([header.len()])
+        .max()
+        .unwrap_or(0);
+    if columns == 0 {
+        return String::new();
+    }
+    let mut widths = vec![0; columns];
+    for row in body.iter().chain([&header]) {
+        for (idx, cell) in row.iter().enumerate() {
+            widths[idx] = widths[idx].max(cell.width());
+        }
+    }
+
+    let render = |row: &[&str]| {
+        let mut line = String::new();
+        for (idx, width) in widths.iter().enumerate() {
+            let cell = row.get(idx).copied().unwrap_or("");
+            if idx > 0 {
+                line.push_str("  ");
+            }
+            line.push_str(cell);
+            line.push_str(&" ".repeat(width - cell.width()));
+        }
+        line.trim_end().to_string()
+    };
+
+    let mut lines = Vec::with_capacity(body.len() + 2);
+    if !header.is_empty() {
+        lines.push(render(&header));
+        let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        lines.push(separator.join("  "));
+    }
+    lines.extend(body.iter().map(|row| render(row)));
+    lines.join("\n")
+}
+
+/// Formats a table like [`format_table`], truncating lines to `max_width` columns.
+///
+/// Pair it with `TerminalApp::terminal_size` so wide tables fit the terminal.
+pub fn format_table_fit<H, C, R>(headers: &[H], rows: &[R], max_width: usize) -> String
+where
+    H: AsRef<str>,
+    C: AsRef<str>,
+    R: AsRef<[C]>,
+{
+    format_table(headers, rows)
+        .lines()
+        .map(|line| truncate_to_width(line, max_width))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Applies the `\r` characters in a single line according to `mode`.
+pub(crate) fn resolve_carriage_returns(line: &str, mode: CarriageReturnMode) -> String {
+    match mode {
+        CarriageReturnMode::Strip => line.replace('\r', ""),
+        CarriageReturnMode::Overwrite => {
+            let mut cells: Vec<char> = Vec::new();
+            for segment in line.split('\r') {
+                for (i, c) in segment.chars().enumerate() {
+                    match cells.get_mut(i) {
+                        Some(cell) => *cell = c,
+                        None => cells.push(c),
+                    }
+                }
+            }
+            cells.into_iter().collect()
+        }
+    }
+}
+
+/// Neutralizes control characters in a single line of command output.
+///
+/// Color and style sequences (`ESC [ ... m`) and OSC 8 hyperlinks are kept; any
+/// other escape sequence and control character is shown in caret notation, so
+/// `ESC [2J` prints as `^[[2J` instead of clearing the screen. Tabs are kept, or
+/// expanded to the next multiple of eight columns with `expand_tabs`.
+pub(crate) fn sanitize_control_chars(line: &str, expand_tabs: bool) -> String {
+    let mut sanitized = String::with_capacity(line.len());
+    let mut column = 0;
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        if c == '\x1b'
+            && let Some(len) = allowed_escape_len(rest)
+        {
+            sanitized.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '\t' if expand_tabs => {
+                let spaces = 8 - column % 8;
+                sanitized.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\t' => {
+                sanitized.push(c);
+                column += 8 - column % 8;
+            }
+            '\x00'..='\x1f' => {
+                sanitized.push('^');
+                sanitized.push(char::from(c as u8 + 0x40));
+                column += 2;
+            }
+            '\x7f' => {
+                sanitized.push_str("^?");
+                column += 2;
+            }
+            '\u{80}'..='\u{9f}' => {
+                let escaped = format!("\\x{:02x}", c as u32);
+                column += escaped.len();
+                sanitized.push_str(&escaped);
+            }
+            c => {
+                sanitized.push(c);
+                column += c.width().unwrap_or(0);
+            }
+        }
+    }
+    sanitized
+}
+
+/// Returns the length of the escape sequence at the start of `text` if it only
+/// styles text: an SGR sequence or an OSC 8 hyperlink.
+fn allowed_escape_len(text: &str) -> Option<usize> {
+    let body = text.strip_prefix('\x1b')?;
+    if let Some(params) = body.strip_prefix('[') {
+        let end = params.find(|c: char| !matches!(c, '0'..='9' | ';' | ':'))?;
+        return (params[end..].starts_with('m')).then_some(end + 3);
+    }
+    let link = body.strip_prefix("]8;")?;
+    let (end, terminator) = [("\x1b\\", 2), ("\x07", 1)]
+        .into_iter()
+        .filter_map(|(st, len)| link.find(st).map(|end| (end, len)))
+        .min()?;
+    // An escape inside the link would end it and start an unchecked sequence
+    if link[..end].chars().any(char::is_control) {
+        return None;
+    }
+    Some(4 + end + terminator)
+}